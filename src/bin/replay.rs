@@ -0,0 +1,47 @@
+// Plays replay files through the headless engine and reports how each game
+// ended, e.g. to check that old recordings still play out the same way.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::process::exit;
+
+use rhombus_instinct::{format_time, Replay};
+
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: replay FILE...");
+        exit(2);
+    }
+    let mut failed = false;
+    for path in paths.iter() {
+        let replay = match File::open(path).and_then(|f| Replay::read(BufReader::new(f))) {
+            Ok(replay) => replay,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+                continue;
+            }
+        };
+        let game = replay.play();
+        let grade = game.grade().map_or("-".to_string(), |g| g.name());
+        println!(
+            "{}: mode {} seed {} frames {} score {} grade {} level {} lines {} time {} pieces {} keys {} state {:?}",
+            path,
+            replay.mode.name(),
+            replay.seed,
+            game.frame(),
+            game.score(),
+            grade,
+            game.level(),
+            game.lines_cleared(),
+            format_time(game.timer().frames()),
+            game.pieces(),
+            game.key_presses(),
+            game.state()
+        );
+    }
+    if failed {
+        exit(1);
+    }
+}
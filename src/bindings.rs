@@ -0,0 +1,256 @@
+// Maps physical inputs onto the things a player can ask for.  Inputs are
+// named by the frontend (the piston frontend uses piston's key names, like
+// "Left" or "Z"), so this module doesn't need to know about any particular
+// input library.  Any number of inputs can be bound to the same command.
+//
+// Bindings are stored as a small subset of TOML, one command per line:
+//
+//   # rhombus-instinct key bindings
+//   left = ["Left", "J"]  # comments can go at the end of a line, too
+//   rotate_left = ["Z"]
+//   "pause" = []
+//   ...
+//
+// Keys may be bare or quoted, and names may use either kind of quotes, but
+// arrays have to fit on one line and escapes aren't supported.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::keys::Action;
+
+// Everything an input can be bound to: the game's own actions, plus a few
+// that are up to the frontend.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Command {
+    Play(Action),
+    Pause,
+    Restart,
+}
+
+impl Command {
+    pub const ALL: [Command; 10] = [
+        Command::Play(Action::Left),
+        Command::Play(Action::Right),
+        Command::Play(Action::SonicDrop),
+        Command::Play(Action::FastDrop),
+        Command::Play(Action::RotateLeft),
+        Command::Play(Action::RotateRight),
+        Command::Play(Action::Rotate180),
+        Command::Play(Action::Hold),
+        Command::Pause,
+        Command::Restart,
+    ];
+
+    // Stable names, for use in files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Play(action) => action.name(),
+            Command::Pause => "pause",
+            Command::Restart => "restart",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::ALL.iter().cloned().find(|c| c.name() == name)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Bindings {
+    bindings: Vec<(String, Command)>, // input name, what it does
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Cuts off a `#` comment, unless the `#` is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+// `"a"` or `'a'`, without escapes.
+fn parse_string(word: &str) -> Option<&str> {
+    for &quote in ['"', '\''].iter() {
+        if let Some(name) = word.strip_prefix(quote).and_then(|w| w.strip_suffix(quote)) {
+            if name.contains(quote) || (quote == '"' && name.contains('\\')) {
+                return None;
+            }
+            return Some(name);
+        }
+    }
+    None
+}
+
+// A bare or quoted key.
+fn parse_key(word: &str) -> &str {
+    let word = word.trim();
+    parse_string(word).unwrap_or(word)
+}
+
+// Parses `["a", "b"]` or a bare `"a"`.
+fn parse_names(value: &str) -> Option<Vec<String>> {
+    let value = value.trim();
+    let list = match value.strip_prefix('[') {
+        Some(rest) => rest.strip_suffix(']')?,
+        None => value,
+    };
+    let mut names = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        names.push(parse_string(item)?.to_string());
+    }
+    Some(names)
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Bindings {
+            bindings: Vec::new(),
+        }
+    }
+
+    // Binds `input` to `command`, replacing whatever it did before.
+    pub fn bind(&mut self, input: &str, command: Command) {
+        self.bindings.retain(|(bound, _)| bound != input);
+        self.bindings.push((input.to_string(), command));
+    }
+    pub fn command(&self, input: &str) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == input)
+            .map(|&(_, command)| command)
+    }
+    pub fn inputs(&self, command: Command) -> Vec<&str> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == command)
+            .map(|(input, _)| input.as_str())
+            .collect()
+    }
+
+    // Where the frontend keeps its bindings: under $XDG_CONFIG_HOME (or
+    // ~/.config) on Unix and %APPDATA% on Windows.
+    pub fn config_path() -> Option<PathBuf> {
+        let dir = if cfg!(windows) {
+            PathBuf::from(std::env::var_os("APPDATA")?)
+        } else {
+            match std::env::var_os("XDG_CONFIG_HOME") {
+                Some(dir) => PathBuf::from(dir),
+                None => Path::new(&std::env::var_os("HOME")?).join(".config"),
+            }
+        };
+        Some(dir.join("rhombus-instinct").join("bindings.toml"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut out = io::BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "# rhombus-instinct key bindings")?;
+        for &command in Command::ALL.iter() {
+            let inputs: Vec<String> = self
+                .inputs(command)
+                .iter()
+                .map(|input| format!("\"{}\"", input))
+                .collect();
+            writeln!(out, "{} = [{}]", command.name(), inputs.join(", "))?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(input: R) -> io::Result<Self> {
+        let mut bindings = Bindings::new();
+        for line in input.lines() {
+            let line = line?;
+            let content = strip_comment(&line).trim();
+            if content.is_empty() {
+                continue;
+            }
+            let bad_line = || invalid(format!("bad bindings line: {:?}", line));
+            let mut parts = content.splitn(2, '=');
+            let command = parts
+                .next()
+                .and_then(|name| Command::from_name(parse_key(name)))
+                .ok_or_else(bad_line)?;
+            let inputs = parts.next().and_then(parse_names).ok_or_else(bad_line)?;
+            for input in inputs {
+                bindings.bind(&input, command);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let mut bindings = Bindings::new();
+        bindings.bind("Left", Command::Play(Action::Left));
+        bindings.bind("J", Command::Play(Action::Left));
+        bindings.bind("Pad LeftStickX-", Command::Play(Action::Left));
+        bindings.bind("Pad South", Command::Play(Action::RotateLeft));
+        bindings.bind("Pad Code 304", Command::Play(Action::Hold));
+        bindings.bind("P", Command::Pause);
+        let mut file = Vec::new();
+        bindings.write(&mut file).unwrap();
+        assert_eq!(Bindings::read(&file[..]).unwrap(), bindings);
+    }
+
+    #[test]
+    fn reads_comments_and_quoted_keys() {
+        let file = "# bindings\n\
+                    left = [\"Left\", 'J'] # arrows, and # vi\n\
+                    \"right\" = [\"Right\",]\n\
+                    'pause' = \"P\"  # just the one\n";
+        let bindings = Bindings::read(file.as_bytes()).unwrap();
+        assert_eq!(bindings.inputs(Command::Play(Action::Left)), ["Left", "J"]);
+        assert_eq!(bindings.inputs(Command::Play(Action::Right)), ["Right"]);
+        assert_eq!(bindings.inputs(Command::Pause), ["P"]);
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_read() {
+        for line in [
+            "jump = [\"Space\"]",
+            "left = [Left]",
+            "left = [\"Left\"",
+            "left = [\"a\\\"b\"]",
+        ]
+        .iter()
+        {
+            assert!(Bindings::read(line.as_bytes()).is_err(), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::sync::Arc;
+
+use crate::piece::{Orientation, Position, Tetrhombino, TetrhombinoState};
+use crate::rotation::{KickCondition, RotationSystem};
+
+pub const BOARD_WIDTH: usize = 10;
+pub const BOARD_HEIGHT: usize = 22;
+
+pub type Board = [[Option<Tetrhombino>; BOARD_HEIGHT]; BOARD_WIDTH];
+
+// How many more frames each locked block stays visible, or `None` if it
+// always will be.
+pub type Visibility = [[Option<usize>; BOARD_HEIGHT]; BOARD_WIDTH];
+
+#[derive(Debug, Clone)]
+pub struct BoardState {
+    board: Board,
+    visibility: Visibility,
+    vanish: Option<usize>, // how long newly locked blocks stay visible
+    current: TetrhombinoState,
+    rotation: Arc<RotationSystem>,
+    floor_kicks: usize, // used by the current piece
+}
+
+impl BoardState {
+    pub fn new(rotation: Arc<RotationSystem>) -> Self {
+        BoardState {
+            rotation,
+            floor_kicks: 0,
+            current: TetrhombinoState {
+                tetrhombino: Tetrhombino::I,
+                orientation: Orientation::Start,
+                position: (100, 100), // off the board.
+            },
+            board: [[None; BOARD_HEIGHT]; BOARD_WIDTH],
+            visibility: [[None; BOARD_HEIGHT]; BOARD_WIDTH],
+            vanish: None,
+        }
+    }
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+    pub fn visibility(&self) -> &Visibility {
+        &self.visibility
+    }
+    // Blocks locked from now on disappear after this many frames.
+    pub fn set_vanish(&mut self, vanish: Option<usize>) {
+        self.vanish = vanish;
+    }
+    // Call once a frame to count down the visibility of vanishing blocks.
+    pub fn tick(&mut self) {
+        for frames in self
+            .visibility
+            .iter_mut()
+            .flat_map(|c| c.iter_mut())
+            .flatten()
+        {
+            *frames = frames.saturating_sub(1);
+        }
+    }
+    pub fn current(&self) -> TetrhombinoState {
+        self.current
+    }
+    pub fn rotation(&self) -> &RotationSystem {
+        &self.rotation
+    }
+    pub fn occupied(&self, pos: Position) -> bool {
+        let (x, y) = pos;
+        if x < 0 || x >= BOARD_WIDTH as i8 || y < 0 || y >= BOARD_HEIGHT as i8 {
+            true
+        } else {
+            self.board[x as usize][y as usize].is_some()
+        }
+    }
+    fn center_column_conflicts(&self) -> bool {
+        let (x, y) = self.current.position;
+        self.occupied((x, y - 1)) || self.occupied((x, y)) || self.occupied((x, y + 1))
+    }
+    pub fn piece_conflicts(&self, state: TetrhombinoState) -> bool {
+        self.rotation
+            .occupied_places(state)
+            .iter()
+            .any(|pos| self.occupied(*pos))
+    }
+    pub fn current_piece_conflicts(&self) -> bool {
+        self.piece_conflicts(self.current)
+    }
+    fn kick_condition_holds(&self, condition: KickCondition) -> bool {
+        let (x, y) = self.current.position;
+        match condition {
+            KickCondition::Always => true,
+            KickCondition::Never => false,
+            KickCondition::CenterClear => !self.center_column_conflicts(),
+            KickCondition::Occupied((dx, dy)) => self.occupied((x + dx, y + dy)),
+        }
+    }
+    fn try_offset(&mut self, (dx, dy): Position) -> bool {
+        self.current.position.0 += dx;
+        self.current.position.1 += dy;
+        if self.current_piece_conflicts() {
+            self.current.position.0 -= dx;
+            self.current.position.1 -= dy;
+            false
+        } else {
+            true
+        }
+    }
+    fn finish_rotate(&mut self, from: Orientation, grounded: bool) -> bool {
+        if !self.current_piece_conflicts() {
+            return true;
+        }
+        let rotation = Arc::clone(&self.rotation);
+        let rule =
+            match rotation.kick_rule(self.current.tetrhombino, from, self.current.orientation) {
+                Some(rule) => rule,
+                None => return false,
+            };
+        if self.kick_condition_holds(rule.condition)
+            && rule.wall.iter().any(|kick| self.try_offset(*kick))
+        {
+            return true;
+        }
+        if grounded
+            && self.floor_kicks < rotation.floor_kick_limit()
+            && rule.floor.iter().any(|kick| self.try_offset(*kick))
+        {
+            self.floor_kicks += 1;
+            return true;
+        }
+        false
+    }
+    pub fn stuck(&mut self) -> bool {
+        self.current.position.1 -= 1;
+        let result = self.current_piece_conflicts();
+        self.current.position.1 += 1;
+        result
+    }
+    pub fn fall(&mut self) -> bool {
+        self.current.position.1 -= 1;
+        if self.current_piece_conflicts() {
+            self.current.position.1 += 1;
+            false
+        } else {
+            true
+        }
+    }
+    pub fn get_shadow(&self) -> TetrhombinoState {
+        let mut res = self.current;
+        while !self.piece_conflicts(res) {
+            res.position.1 -= 1;
+        }
+        res.position.1 += 1;
+        res
+    }
+    pub fn flip_right(&mut self) -> bool {
+        let grounded = self.stuck();
+        let from = self.current.orientation;
+        self.current.orientation.flip_right();
+        if !self.finish_rotate(from, grounded) {
+            self.current.orientation.flip_right();
+            false
+        } else {
+            true
+        }
+    }
+    pub fn flip_left(&mut self) -> bool {
+        let grounded = self.stuck();
+        let from = self.current.orientation;
+        self.current.orientation.flip_left();
+        if !self.finish_rotate(from, grounded) {
+            self.current.orientation.flip_left();
+            false
+        } else {
+            true
+        }
+    }
+    pub fn flip_both(&mut self) -> bool {
+        let grounded = self.stuck();
+        let from = self.current.orientation;
+        self.current.orientation.flip_both();
+        if !self.finish_rotate(from, grounded) {
+            self.current.orientation.flip_both();
+            false
+        } else {
+            true
+        }
+    }
+    pub fn shift_left(&mut self) -> bool {
+        self.current.position.0 -= 1;
+        if self.current_piece_conflicts() {
+            self.current.position.0 += 1;
+            false
+        } else {
+            true
+        }
+    }
+    pub fn shift_right(&mut self) -> bool {
+        self.current.position.0 += 1;
+        if self.current_piece_conflicts() {
+            self.current.position.0 -= 1;
+            false
+        } else {
+            true
+        }
+    }
+    pub fn lock(&mut self) {
+        for (x, y) in self.rotation.occupied_places(self.current).iter() {
+            self.board[(*x) as usize][(*y) as usize] = Some(self.current.tetrhombino);
+            self.visibility[(*x) as usize][(*y) as usize] = self.vanish;
+        }
+    }
+    pub fn clear(&mut self) -> usize {
+        let mut cursor: usize = 0;
+        let mut result = 0;
+        for read in 0..BOARD_HEIGHT {
+            if (0..BOARD_WIDTH).any(|x| self.board[x][read].is_none()) {
+                for x in 0..BOARD_WIDTH {
+                    self.board[x][cursor] = self.board[x][read];
+                    self.visibility[x][cursor] = self.visibility[x][read];
+                }
+                cursor += 1;
+            } else {
+                result += 1;
+            }
+        }
+        for write in cursor..BOARD_HEIGHT {
+            for x in 0..BOARD_WIDTH {
+                self.board[x][write] = None;
+                self.visibility[x][write] = None;
+            }
+        }
+        result
+    }
+    pub fn empty(&self) -> bool {
+        self.board
+            .iter()
+            .all(|column| column.iter().all(Option::is_none))
+    }
+    // Pushes the whole stack up one row, copying the bottom row into the gap.
+    // Returns false if that pushed any blocks off the top of the board.
+    pub fn raise_garbage(&mut self) -> bool {
+        let overflow = (0..BOARD_WIDTH).any(|x| self.board[x][BOARD_HEIGHT - 1].is_some());
+        for column in self.board.iter_mut() {
+            for y in (1..BOARD_HEIGHT).rev() {
+                column[y] = column[y - 1];
+            }
+        }
+        for column in self.visibility.iter_mut() {
+            for y in (1..BOARD_HEIGHT).rev() {
+                column[y] = column[y - 1];
+            }
+        }
+        !overflow
+    }
+    // Empties the whole stack.
+    pub fn clear_all(&mut self) {
+        self.board = [[None; BOARD_HEIGHT]; BOARD_WIDTH];
+        self.visibility = [[None; BOARD_HEIGHT]; BOARD_WIDTH];
+    }
+    pub fn spawn(&mut self, new_piece: TetrhombinoState) {
+        self.current = new_piece;
+        self.floor_kicks = 0;
+    }
+}
+
+// Proper game logic:
+// - spawn
+// - current_piece_conflicts [determine game end]
+// - flip_right, flip_left, shift_right, shift_left, fall
+// - lock
+// - clear
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(
+        rotation: RotationSystem,
+        tetrhombino: Tetrhombino,
+        orientation: Orientation,
+        position: Position,
+    ) -> BoardState {
+        let mut board = BoardState::new(Arc::new(rotation));
+        board.spawn(TetrhombinoState {
+            tetrhombino,
+            orientation,
+            position,
+        });
+        board
+    }
+
+    fn place(board: &BoardState) -> (Orientation, Position) {
+        (board.current().orientation, board.current().position)
+    }
+
+    #[test]
+    fn ti_kicks_the_i_off_walls() {
+        let mut board = board_with(
+            RotationSystem::ti(),
+            Tetrhombino::I,
+            Orientation::Right,
+            (-1, 10),
+        );
+        assert!(board.flip_right());
+        assert_eq!(place(&board), (Orientation::Start, (1, 10)));
+
+        let mut board = board_with(
+            RotationSystem::ti(),
+            Tetrhombino::I,
+            Orientation::Right,
+            (8, 10),
+        );
+        assert!(board.flip_right());
+        assert_eq!(place(&board), (Orientation::Start, (7, 10)));
+    }
+
+    #[test]
+    fn classic_never_kicks_the_i() {
+        let mut board = board_with(
+            RotationSystem::classic(),
+            Tetrhombino::I,
+            Orientation::Right,
+            (-1, 10),
+        );
+        assert!(!board.flip_right());
+        assert_eq!(place(&board), (Orientation::Right, (-1, 10)));
+    }
+
+    #[test]
+    fn ti_floor_kicks_once_per_piece() {
+        let mut board = board_with(
+            RotationSystem::ti(),
+            Tetrhombino::I,
+            Orientation::Start,
+            (4, 0),
+        );
+        assert!(board.flip_right());
+        assert_eq!(place(&board), (Orientation::Right, (4, 2)));
+        assert!(board.flip_right());
+        while board.fall() {}
+        assert_eq!(place(&board), (Orientation::Start, (4, 0)));
+        assert!(!board.flip_right());
+        assert_eq!(place(&board), (Orientation::Start, (4, 0)));
+
+        // The next piece gets a fresh kick.
+        board.spawn(board.current());
+        assert!(board.flip_right());
+        assert_eq!(place(&board), (Orientation::Right, (4, 2)));
+    }
+
+    #[test]
+    fn srs_kicks_the_i_by_its_own_table() {
+        let mut board = board_with(
+            RotationSystem::srs(),
+            Tetrhombino::I,
+            Orientation::Left,
+            (0, 10),
+        );
+        assert!(board.flip_left());
+        assert_eq!(place(&board), (Orientation::Start, (1, 10)));
+    }
+
+    #[test]
+    fn srs_kicks_the_other_pieces_by_the_common_table() {
+        // With the first kick, one to the left, blocked, the second one
+        // takes the T down as well.
+        let mut board = board_with(
+            RotationSystem::srs(),
+            Tetrhombino::T,
+            Orientation::Left,
+            (9, 10),
+        );
+        board.board[8][11] = Some(Tetrhombino::O);
+        assert!(board.flip_left());
+        assert_eq!(place(&board), (Orientation::Start, (8, 9)));
+    }
+
+    #[test]
+    fn ti_half_turns_never_kick() {
+        let mut board = board_with(
+            RotationSystem::ti(),
+            Tetrhombino::T,
+            Orientation::Start,
+            (5, 5),
+        );
+        board.board[4][4] = Some(Tetrhombino::O);
+        assert!(!board.flip_both());
+        assert_eq!(place(&board), (Orientation::Start, (5, 5)));
+    }
+
+    #[test]
+    fn srs_half_turns_kick_by_their_own_table() {
+        let mut board = board_with(
+            RotationSystem::srs(),
+            Tetrhombino::T,
+            Orientation::Start,
+            (5, 5),
+        );
+        board.board[5][4] = Some(Tetrhombino::O);
+        assert!(board.flip_both());
+        assert_eq!(place(&board), (Orientation::Both, (5, 6)));
+    }
+}
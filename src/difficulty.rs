@@ -0,0 +1,585 @@
+use crate::keys::{DEFAULT_ARR_FRAMES, DEFAULT_DAS_FRAMES};
+use crate::timer::Sections;
+
+// Gravity is measured in G/256; anything at or above this drops the piece
+// straight to the stack.
+pub const TWENTY_G: usize = 20 * 256;
+
+pub trait DifficultyCurve {
+    fn get_gravity(&self) -> usize; // units are G/256
+    fn get_are_frames(&self) -> usize;
+    fn get_line_are_frames(&self) -> usize; // ARE after a line clear
+    fn get_clear_frames(&self) -> usize;
+    fn get_lock_frames(&self) -> usize;
+    fn get_das_frames(&self) -> usize;
+    fn clear_lines(&mut self, lines: usize);
+    fn set_level(&mut self, level: usize); // called whenever the level changes
+    fn max_level(&self) -> usize;
+    fn done(&self) -> bool;
+
+    fn get_arr_frames(&self) -> usize {
+        DEFAULT_ARR_FRAMES
+    }
+    // What the timer's splits are measured in.
+    fn sections(&self) -> Sections {
+        Sections::Levels
+    }
+    // Called once per frame with the time on the game clock, in frames.
+    fn tick(&mut self, _frame: u64) {}
+    // Called every time a piece locks.  Returns how many rows of garbage to
+    // push up from the bottom of the board.
+    fn garbage(&mut self, _lines_cleared: usize) -> usize {
+        0
+    }
+    // True if the player missed a time limit and the game should end early.
+    fn cut_off(&self) -> bool {
+        false
+    }
+}
+
+impl<D: DifficultyCurve + ?Sized> DifficultyCurve for Box<D> {
+    fn get_gravity(&self) -> usize {
+        (**self).get_gravity()
+    }
+    fn get_are_frames(&self) -> usize {
+        (**self).get_are_frames()
+    }
+    fn get_line_are_frames(&self) -> usize {
+        (**self).get_line_are_frames()
+    }
+    fn get_clear_frames(&self) -> usize {
+        (**self).get_clear_frames()
+    }
+    fn get_lock_frames(&self) -> usize {
+        (**self).get_lock_frames()
+    }
+    fn get_das_frames(&self) -> usize {
+        (**self).get_das_frames()
+    }
+    fn clear_lines(&mut self, lines: usize) {
+        (**self).clear_lines(lines)
+    }
+    fn set_level(&mut self, level: usize) {
+        (**self).set_level(level)
+    }
+    fn max_level(&self) -> usize {
+        (**self).max_level()
+    }
+    fn done(&self) -> bool {
+        (**self).done()
+    }
+    fn get_arr_frames(&self) -> usize {
+        (**self).get_arr_frames()
+    }
+    fn sections(&self) -> Sections {
+        (**self).sections()
+    }
+    fn tick(&mut self, frame: u64) {
+        (**self).tick(frame)
+    }
+    fn garbage(&mut self, lines_cleared: usize) -> usize {
+        (**self).garbage(lines_cleared)
+    }
+    fn cut_off(&self) -> bool {
+        (**self).cut_off()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct NormalDifficulty {
+    level: usize,
+}
+
+impl NormalDifficulty {
+    pub fn new() -> Self {
+        NormalDifficulty { level: 0 }
+    }
+}
+
+impl Default for NormalDifficulty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifficultyCurve for NormalDifficulty {
+    fn get_gravity(&self) -> usize {
+        const SPEED_TABLE: [usize; 10] = [4, 8, 12, 16, 24, 32, 48, 64, 128, 256];
+        SPEED_TABLE[(self.level / 100).min(SPEED_TABLE.len() - 1)]
+    }
+    fn get_are_frames(&self) -> usize {
+        25
+    }
+    fn get_line_are_frames(&self) -> usize {
+        25
+    }
+    fn get_clear_frames(&self) -> usize {
+        40
+    }
+    fn get_lock_frames(&self) -> usize {
+        30
+    }
+    fn get_das_frames(&self) -> usize {
+        DEFAULT_DAS_FRAMES
+    }
+    fn clear_lines(&mut self, _lines: usize) {}
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+    }
+    fn max_level(&self) -> usize {
+        999
+    }
+    fn done(&self) -> bool {
+        self.level >= self.max_level()
+    }
+}
+
+// Finds the entry for `level` in a table sorted by starting level.
+fn lookup<T: Copy>(table: &[(usize, T)], level: usize) -> T {
+    table
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= level)
+        .map(|(_, value)| *value)
+        .unwrap_or(table[0].1)
+}
+
+// The internal gravity table from TGM1 and TGM2's Master mode, in G/256.
+// Note the reset at 200, and the jump to 20G at 500.
+const MASTER_GRAVITY: [(usize, usize); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, TWENTY_G),
+];
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+struct Timings {
+    are: usize,
+    line_are: usize,
+    das: usize,
+    lock: usize,
+    clear: usize,
+}
+
+// TGM2 Master mode delays, in frames.
+#[rustfmt::skip]
+const MASTER_TIMINGS: [(usize, Timings); 6] = [
+    (0,   Timings { are: 25, line_are: 25, das: 14, lock: 30, clear: 40 }),
+    (500, Timings { are: 25, line_are: 25, das: 8,  lock: 30, clear: 25 }),
+    (600, Timings { are: 25, line_are: 16, das: 8,  lock: 30, clear: 16 }),
+    (700, Timings { are: 16, line_are: 12, das: 8,  lock: 30, clear: 12 }),
+    (800, Timings { are: 12, line_are: 6,  das: 8,  lock: 30, clear: 6 }),
+    (900, Timings { are: 12, line_are: 6,  das: 6,  lock: 17, clear: 6 }),
+];
+
+#[derive(Debug, Copy, Clone)]
+pub struct MasterDifficulty {
+    level: usize,
+}
+
+impl MasterDifficulty {
+    pub fn new() -> Self {
+        MasterDifficulty { level: 0 }
+    }
+    fn timings(&self) -> Timings {
+        lookup(&MASTER_TIMINGS, self.level)
+    }
+}
+
+impl Default for MasterDifficulty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifficultyCurve for MasterDifficulty {
+    fn get_gravity(&self) -> usize {
+        lookup(&MASTER_GRAVITY, self.level)
+    }
+    fn get_are_frames(&self) -> usize {
+        self.timings().are
+    }
+    fn get_line_are_frames(&self) -> usize {
+        self.timings().line_are
+    }
+    fn get_clear_frames(&self) -> usize {
+        self.timings().clear
+    }
+    fn get_lock_frames(&self) -> usize {
+        self.timings().lock
+    }
+    fn get_das_frames(&self) -> usize {
+        self.timings().das
+    }
+    fn clear_lines(&mut self, _lines: usize) {}
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+    }
+    fn max_level(&self) -> usize {
+        999
+    }
+    fn done(&self) -> bool {
+        self.level >= self.max_level()
+    }
+}
+
+// A "torikan": reaching `level` after `frame` ends the game.
+type TimeLimit = (usize, u64);
+
+// Tracks whether the level has crossed any of `limits` too late.
+#[derive(Debug, Copy, Clone)]
+struct Torikan {
+    limits: &'static [TimeLimit],
+    level: usize,
+    frame: u64,
+    cut_off: bool,
+}
+
+impl Torikan {
+    fn new(limits: &'static [TimeLimit]) -> Self {
+        Torikan {
+            limits,
+            level: 0,
+            frame: 0,
+            cut_off: false,
+        }
+    }
+    fn set_level(&mut self, level: usize) {
+        for (limit_level, limit_frame) in self.limits.iter() {
+            if self.level < *limit_level && level >= *limit_level && self.frame > *limit_frame {
+                self.cut_off = true;
+            }
+        }
+        self.level = level;
+    }
+}
+
+// TGM2 Death mode: 20G from the start, with the delays shrinking every
+// section.  Reaching 500 after 3:25 ends the game.
+#[rustfmt::skip]
+const DEATH_TIMINGS: [(usize, Timings); 6] = [
+    (0,   Timings { are: 18, line_are: 14, das: 12, lock: 30, clear: 12 }),
+    (100, Timings { are: 14, line_are: 8,  das: 12, lock: 26, clear: 6 }),
+    (200, Timings { are: 14, line_are: 8,  das: 11, lock: 22, clear: 6 }),
+    (300, Timings { are: 8,  line_are: 8,  das: 10, lock: 18, clear: 6 }),
+    (400, Timings { are: 7,  line_are: 7,  das: 8,  lock: 15, clear: 5 }),
+    (500, Timings { are: 6,  line_are: 6,  das: 8,  lock: 15, clear: 4 }),
+];
+
+const DEATH_TORIKAN: [TimeLimit; 1] = [(500, (3 * 60 + 25) * 60)];
+
+#[derive(Debug, Copy, Clone)]
+pub struct DeathDifficulty {
+    level: usize,
+    torikan: Torikan,
+}
+
+impl DeathDifficulty {
+    pub fn new() -> Self {
+        DeathDifficulty {
+            level: 0,
+            torikan: Torikan::new(&DEATH_TORIKAN),
+        }
+    }
+    fn timings(&self) -> Timings {
+        lookup(&DEATH_TIMINGS, self.level)
+    }
+}
+
+impl Default for DeathDifficulty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifficultyCurve for DeathDifficulty {
+    fn get_gravity(&self) -> usize {
+        TWENTY_G
+    }
+    fn get_are_frames(&self) -> usize {
+        self.timings().are
+    }
+    fn get_line_are_frames(&self) -> usize {
+        self.timings().line_are
+    }
+    fn get_clear_frames(&self) -> usize {
+        self.timings().clear
+    }
+    fn get_lock_frames(&self) -> usize {
+        self.timings().lock
+    }
+    fn get_das_frames(&self) -> usize {
+        self.timings().das
+    }
+    fn clear_lines(&mut self, _lines: usize) {}
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+        self.torikan.set_level(level);
+    }
+    fn max_level(&self) -> usize {
+        999
+    }
+    fn done(&self) -> bool {
+        self.level >= self.max_level()
+    }
+    fn tick(&mut self, frame: u64) {
+        self.torikan.frame = frame;
+    }
+    fn cut_off(&self) -> bool {
+        self.torikan.cut_off
+    }
+}
+
+// TGM3 Shirase-style: 20G throughout and even shorter delays, up to level
+// 1300.  From 500 to 999 the bottom row is copied upwards whenever too many
+// pieces are placed without clearing lines.
+#[rustfmt::skip]
+const SHIRASE_TIMINGS: [(usize, Timings); 8] = [
+    (0,    Timings { are: 12, line_are: 8, das: 10, lock: 18, clear: 6 }),
+    (100,  Timings { are: 12, line_are: 7, das: 10, lock: 18, clear: 5 }),
+    (200,  Timings { are: 12, line_are: 6, das: 9,  lock: 17, clear: 4 }),
+    (300,  Timings { are: 6,  line_are: 6, das: 8,  lock: 15, clear: 4 }),
+    (400,  Timings { are: 5,  line_are: 5, das: 8,  lock: 13, clear: 3 }),
+    (500,  Timings { are: 4,  line_are: 4, das: 8,  lock: 12, clear: 3 }),
+    (600,  Timings { are: 4,  line_are: 4, das: 8,  lock: 10, clear: 3 }),
+    (1100, Timings { are: 4,  line_are: 4, das: 8,  lock: 8,  clear: 3 }),
+];
+
+// Pieces that may be placed without clearing before a garbage row rises; 0
+// means no garbage.
+const SHIRASE_GARBAGE_QUOTA: [(usize, usize); 7] = [
+    (0, 0),
+    (500, 20),
+    (600, 18),
+    (700, 10),
+    (800, 9),
+    (900, 8),
+    (1000, 0),
+];
+
+const SHIRASE_TORIKAN: [TimeLimit; 2] = [(500, (2 * 60 + 28) * 60), (1000, (4 * 60 + 56) * 60)];
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShiraseDifficulty {
+    level: usize,
+    garbage_count: usize,
+    torikan: Torikan,
+}
+
+impl ShiraseDifficulty {
+    pub fn new() -> Self {
+        ShiraseDifficulty {
+            level: 0,
+            garbage_count: 0,
+            torikan: Torikan::new(&SHIRASE_TORIKAN),
+        }
+    }
+    fn timings(&self) -> Timings {
+        lookup(&SHIRASE_TIMINGS, self.level)
+    }
+}
+
+impl Default for ShiraseDifficulty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifficultyCurve for ShiraseDifficulty {
+    fn get_gravity(&self) -> usize {
+        TWENTY_G
+    }
+    fn get_are_frames(&self) -> usize {
+        self.timings().are
+    }
+    fn get_line_are_frames(&self) -> usize {
+        self.timings().line_are
+    }
+    fn get_clear_frames(&self) -> usize {
+        self.timings().clear
+    }
+    fn get_lock_frames(&self) -> usize {
+        self.timings().lock
+    }
+    fn get_das_frames(&self) -> usize {
+        self.timings().das
+    }
+    fn clear_lines(&mut self, _lines: usize) {}
+    fn set_level(&mut self, level: usize) {
+        self.level = level;
+        self.torikan.set_level(level);
+    }
+    fn max_level(&self) -> usize {
+        1300
+    }
+    fn done(&self) -> bool {
+        self.level >= self.max_level()
+    }
+    fn tick(&mut self, frame: u64) {
+        self.torikan.frame = frame;
+    }
+    fn garbage(&mut self, lines_cleared: usize) -> usize {
+        let quota = lookup(&SHIRASE_GARBAGE_QUOTA, self.level);
+        if quota == 0 {
+            self.garbage_count = 0;
+            return 0;
+        }
+        if lines_cleared > 0 {
+            self.garbage_count = self.garbage_count.saturating_sub(lines_cleared);
+            return 0;
+        }
+        self.garbage_count += 1;
+        if self.garbage_count >= quota {
+            self.garbage_count = 0;
+            1
+        } else {
+            0
+        }
+    }
+    fn cut_off(&self) -> bool {
+        self.torikan.cut_off
+    }
+}
+
+pub const SPRINT_LINES: usize = 40;
+
+// Sprint: clear SPRINT_LINES lines as fast as possible.  The speed never
+// changes, and the game is over as soon as enough lines are gone.
+#[derive(Debug, Copy, Clone)]
+pub struct SprintDifficulty {
+    lines: usize,
+}
+
+impl SprintDifficulty {
+    pub fn new() -> Self {
+        SprintDifficulty { lines: 0 }
+    }
+}
+
+impl Default for SprintDifficulty {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DifficultyCurve for SprintDifficulty {
+    fn get_gravity(&self) -> usize {
+        4
+    }
+    fn get_are_frames(&self) -> usize {
+        6
+    }
+    fn get_line_are_frames(&self) -> usize {
+        6
+    }
+    fn get_clear_frames(&self) -> usize {
+        6
+    }
+    fn get_lock_frames(&self) -> usize {
+        30
+    }
+    fn get_das_frames(&self) -> usize {
+        DEFAULT_DAS_FRAMES
+    }
+    fn clear_lines(&mut self, lines: usize) {
+        self.lines += lines;
+    }
+    fn set_level(&mut self, _level: usize) {}
+    fn max_level(&self) -> usize {
+        999
+    }
+    fn done(&self) -> bool {
+        self.lines >= SPRINT_LINES
+    }
+    fn sections(&self) -> Sections {
+        Sections::Lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: [TimeLimit; 2] = [(500, 1000), (1000, 2000)];
+
+    fn reach(torikan: &mut Torikan, level: usize, frame: u64) {
+        torikan.frame = frame;
+        torikan.set_level(level);
+    }
+
+    #[test]
+    fn torikan_allows_reaching_the_level_on_time() {
+        let mut torikan = Torikan::new(&LIMITS);
+        reach(&mut torikan, 499, 900);
+        reach(&mut torikan, 500, 1000);
+        assert!(!torikan.cut_off);
+    }
+
+    #[test]
+    fn torikan_cuts_off_one_frame_late() {
+        let mut torikan = Torikan::new(&LIMITS);
+        reach(&mut torikan, 499, 900);
+        reach(&mut torikan, 500, 1001);
+        assert!(torikan.cut_off);
+    }
+
+    #[test]
+    fn torikan_checks_levels_skipped_over() {
+        let mut torikan = Torikan::new(&LIMITS);
+        reach(&mut torikan, 498, 900);
+        reach(&mut torikan, 502, 1001);
+        assert!(torikan.cut_off);
+    }
+
+    #[test]
+    fn torikan_only_checks_when_the_level_is_reached() {
+        let mut torikan = Torikan::new(&LIMITS);
+        reach(&mut torikan, 499, 1500);
+        assert!(!torikan.cut_off);
+        reach(&mut torikan, 500, 1000);
+        reach(&mut torikan, 600, 1999);
+        reach(&mut torikan, 999, 2500);
+        assert!(!torikan.cut_off);
+        reach(&mut torikan, 1000, 2001);
+        assert!(torikan.cut_off);
+    }
+
+    #[test]
+    fn death_cuts_off_at_500_after_3_25() {
+        let limit = DEATH_TORIKAN[0].1;
+        for &(frame, cut_off) in [(limit, false), (limit + 1, true)].iter() {
+            let mut death = DeathDifficulty::new();
+            death.set_level(499);
+            death.tick(frame);
+            death.set_level(500);
+            assert_eq!(death.cut_off(), cut_off);
+        }
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::board::BoardState;
+use crate::difficulty::{DifficultyCurve, NormalDifficulty, TWENTY_G};
+use crate::grade::{Grade, Grader};
+use crate::keys::{Action, Handling, KeyState, DEFAULT_ARR_FRAMES, DEFAULT_DAS_FRAMES};
+use crate::level::LevelCounter;
+use crate::lock::LockDelay;
+use crate::piece::{Orientation, Tetrhombino, TetrhombinoState, START_POSITION};
+use crate::randomizer::{Randomizer, TGMRandomizer};
+use crate::roll::{CreditRoll, Roll};
+use crate::rules::Rules;
+use crate::score::Score;
+use crate::timer::Timer;
+
+// The engine advances exactly one frame per call to `Game::update`; all of the
+// frame counts in this crate are in units of 1/FRAMES_PER_SECOND seconds.
+pub const FRAMES_PER_SECOND: u64 = 60;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum State {
+    Start,
+    Falling,
+    Are(usize),       // frame count
+    Clear(usize),     // frame count
+    RollStart(usize), // frame count; the pause before the credit roll
+    Loss,
+    Victory,
+}
+
+// How a finished game turned out.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GameResult {
+    pub victory: bool,
+    pub score: usize,
+    pub grade: Option<Grade>,
+    pub level: usize,
+    pub lines: usize,
+    pub frames: u64,
+    pub time: u64,        // frames on the game clock
+    pub splits: Vec<u64>, // frames per section
+    pub pieces: usize,    // pieces locked
+    pub key_presses: usize,
+}
+
+impl GameResult {
+    // By the game clock; 0 if it never started.
+    pub fn pieces_per_second(&self) -> f64 {
+        if self.time == 0 {
+            return 0.0;
+        }
+        self.pieces as f64 * FRAMES_PER_SECOND as f64 / self.time as f64
+    }
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.pieces == 0 {
+            return 0.0;
+        }
+        self.key_presses as f64 / self.pieces as f64
+    }
+}
+
+// A mode is a choice of randomizer, difficulty curve and rules.
+#[derive(Debug, Clone)]
+pub struct Game<R = TGMRandomizer, D = NormalDifficulty> {
+    board: BoardState,
+    rand: R,
+    keys: KeyState,
+    handling: Handling,
+    state: State,
+    lock_delay: LockDelay,
+    gravity_count: usize,
+    are_frames: usize,           // length of the current ARE
+    next: VecDeque<Tetrhombino>, // always `rules.preview()` long once started
+    held: Option<Tetrhombino>,
+    hold_used: bool, // only one hold per piece
+    lines_cleared: usize,
+    pieces: usize,      // pieces locked
+    key_presses: usize, // presses, not counting releases or repeats
+    level: LevelCounter,
+    score: Score,
+    grader: Grader,
+    timer: Timer,
+    roll: Option<Roll>, // from the pause before the credit roll on
+    stage: D,
+    rules: Rules,
+    frame: u64,
+}
+
+impl Game {
+    pub fn new(seed: u64) -> Game {
+        Game::with_mode(
+            TGMRandomizer::new(seed),
+            NormalDifficulty::new(),
+            Rules::default(),
+        )
+    }
+}
+
+impl<R: Randomizer, D: DifficultyCurve> Game<R, D> {
+    pub fn with_mode(rand: R, stage: D, rules: Rules) -> Self {
+        Game {
+            board: BoardState::new(Arc::clone(&rules.rotation)),
+            rand,
+            keys: KeyState::new(),
+            handling: Handling::new(),
+            state: State::Start,
+            lock_delay: LockDelay::new(rules.lock_reset),
+            gravity_count: 0,
+            are_frames: 0,
+            next: VecDeque::new(),
+            held: None,
+            hold_used: false,
+            lines_cleared: 0,
+            pieces: 0,
+            key_presses: 0,
+            level: LevelCounter::new(stage.max_level()),
+            score: Score::new(),
+            grader: Grader::new(rules.grading),
+            timer: Timer::new(),
+            roll: None,
+            stage,
+            rules,
+            frame: 0,
+        }
+    }
+    pub fn seed(&self) -> u64 {
+        self.rand.seed()
+    }
+    pub fn randomizer(&self) -> &R {
+        &self.rand
+    }
+    pub fn difficulty(&self) -> &D {
+        &self.stage
+    }
+    pub fn board(&self) -> &BoardState {
+        &self.board
+    }
+    pub fn state(&self) -> State {
+        self.state
+    }
+    // The upcoming pieces, soonest first.  Empty before the game starts.
+    pub fn next(&self) -> &VecDeque<Tetrhombino> {
+        &self.next
+    }
+    fn take_next(&mut self) -> Tetrhombino {
+        self.next.push_back(self.rand.get_piece());
+        self.next.pop_front().unwrap()
+    }
+    pub fn held(&self) -> Option<Tetrhombino> {
+        self.held
+    }
+    // Whether the hold button is available for the current piece.
+    pub fn can_hold(&self) -> bool {
+        self.rules.hold && !self.hold_used
+    }
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+    pub fn handling(&self) -> Handling {
+        self.handling
+    }
+    pub fn set_handling(&mut self, handling: Handling) {
+        self.handling = handling;
+    }
+    pub fn lines_cleared(&self) -> usize {
+        self.lines_cleared
+    }
+    pub fn pieces(&self) -> usize {
+        self.pieces
+    }
+    pub fn key_presses(&self) -> usize {
+        self.key_presses
+    }
+    pub fn level(&self) -> usize {
+        self.level.level()
+    }
+    pub fn score(&self) -> usize {
+        self.score.score()
+    }
+    // None if the rules don't grade.
+    pub fn grade(&self) -> Option<Grade> {
+        self.grader.grade()
+    }
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+    // Frames left in the credit roll, if it has started.
+    pub fn roll_frames_left(&self) -> Option<usize> {
+        self.roll.and_then(|roll| roll.frames_left())
+    }
+    // The number of times `update` has been called.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+    // None until the game is over.
+    pub fn result(&self) -> Option<GameResult> {
+        let victory = match self.state {
+            State::Victory => true,
+            State::Loss => false,
+            _ => return None,
+        };
+        Some(GameResult {
+            victory,
+            score: self.score(),
+            grade: self.grade(),
+            level: self.level(),
+            lines: self.lines_cleared,
+            frames: self.frame,
+            time: self.timer.frames(),
+            splits: self.timer.splits().to_vec(),
+            pieces: self.pieces,
+            key_presses: self.key_presses,
+        })
+    }
+    // Holding a rotation button as the piece spawns rotates it before its
+    // first frame (IRS), as long as the rotated piece fits.  Holding both
+    // directions is a half turn, just like the 180 button.
+    fn initial_rotation(&mut self, unrotated: TetrhombinoState) -> TetrhombinoState {
+        let mut rotated = unrotated;
+        let (left, right) = (self.keys.r_left.held(), self.keys.r_right.held());
+        if self.keys.r_180.held() || (left && right) {
+            rotated.orientation.flip_both();
+        } else if left {
+            rotated.orientation.flip_left();
+        } else if right {
+            rotated.orientation.flip_right();
+        } else {
+            return unrotated;
+        }
+        if self.board.piece_conflicts(rotated) {
+            return unrotated;
+        }
+        // Don't rotate a second time on the first frame.
+        self.keys.r_left.service();
+        self.keys.r_right.service();
+        self.keys.r_180.service();
+        rotated
+    }
+    // Puts `tetrhombino` in the hold slot, and returns what should replace it.
+    fn swap_hold(&mut self, tetrhombino: Tetrhombino) -> Tetrhombino {
+        self.hold_used = true;
+        match self.held.replace(tetrhombino) {
+            Some(held) => held,
+            None => self.take_next(),
+        }
+    }
+    fn spawn(&mut self) {
+        self.timer.start();
+        let mut tetrhombino = self.take_next();
+        self.hold_used = false;
+        // Initial hold: swap before the piece ever appears.
+        if self.rules.initial_hold && self.can_hold() && self.keys.hold.held() {
+            self.keys.hold.service();
+            tetrhombino = self.swap_hold(tetrhombino);
+        }
+        let piece = self.initial_rotation(TetrhombinoState {
+            tetrhombino,
+            orientation: Orientation::Start,
+            position: START_POSITION,
+        });
+        self.board.spawn(piece);
+        if self.board.current_piece_conflicts() {
+            self.lose();
+            return;
+        }
+        self.level.spawn();
+        self.score.spawn();
+        self.set_level();
+        self.state = State::Falling;
+        self.lock_delay.spawn(self.board.current().position.1);
+        self.gravity_count = 0;
+        self.keys.cut_das(self.handling.das_cut);
+        self.instant_gravity();
+    }
+    fn hold(&mut self) {
+        let tetrhombino = self.swap_hold(self.board.current().tetrhombino);
+        self.board.spawn(TetrhombinoState {
+            tetrhombino,
+            orientation: Orientation::Start,
+            position: START_POSITION,
+        });
+        if self.board.current_piece_conflicts() {
+            self.lose();
+            return;
+        }
+        self.lock_delay.spawn(self.board.current().position.1);
+        self.gravity_count = 0;
+        self.keys.cut_das(self.handling.das_cut);
+        self.instant_gravity();
+    }
+    // Shifts the piece up to `shifts` columns, stopping once it is blocked.
+    fn shift(&mut self, shifts: usize, shift: fn(&mut BoardState) -> bool) {
+        for _ in 0..shifts {
+            let moved = shift(&mut self.board);
+            if moved {
+                self.lock_delay.moved();
+            }
+            self.instant_gravity();
+            if !moved {
+                break;
+            }
+        }
+    }
+    // Successful rotations cut DAS, if the player asked for that.
+    fn rotated(&mut self, rotated: bool) {
+        if rotated {
+            self.lock_delay.moved();
+            self.keys.cut_das(self.handling.das_cut);
+        }
+        self.instant_gravity();
+    }
+    // At 20G the piece is always on the stack: it lands as soon as it spawns,
+    // and again after every move, so it can never be slid over a gap.
+    fn instant_gravity(&mut self) {
+        if self.stage.get_gravity() >= TWENTY_G {
+            while self.board.fall() {}
+        }
+    }
+    fn set_level(&mut self) {
+        let level = self.level.level();
+        self.stage.set_level(level);
+        let section = self.stage.sections().section(level, self.lines_cleared);
+        self.timer.reach_section(section);
+        self.grader
+            .set_level(level, self.score.score(), self.timer.frames());
+    }
+    fn lock(&mut self) {
+        self.board.lock();
+        self.pieces += 1;
+        let cleared = self.board.clear();
+        let level = self.level.level();
+        self.score
+            .lock(level, cleared, cleared > 0 && self.board.empty());
+        self.grader.lock(level, cleared, self.score.score());
+        if cleared > 0 {
+            self.lines_cleared += cleared;
+            self.stage.clear_lines(cleared);
+            self.level.clear_lines(cleared);
+            self.set_level();
+            self.state = State::Clear(0);
+        } else {
+            self.state = State::Are(0);
+            self.are_frames = self.stage.get_are_frames();
+        }
+        for _ in 0..self.stage.garbage(cleared) {
+            if !self.board.raise_garbage() {
+                self.lose();
+                return;
+            }
+        }
+        if let Some(roll) = self.roll.as_mut() {
+            roll.clear_lines(cleared);
+        } else if self.stage.done() {
+            self.timer.finish();
+            if self.rules.credit_roll == CreditRoll::Off {
+                self.win(true);
+            } else {
+                // The stack is cleared away for the roll.
+                let roll = Roll::new(self.rules.credit_roll);
+                self.board.clear_all();
+                self.board.set_vanish(roll.vanish());
+                self.roll = Some(roll);
+                self.state = State::RollStart(0);
+            }
+        } else if self.stage.cut_off() {
+            self.lose();
+        }
+    }
+    // The clock stops here too, so the section the player died in still gets
+    // a split.
+    fn lose(&mut self) {
+        self.timer.finish();
+        self.state = State::Loss;
+    }
+    // Topping out during the credit roll still counts as a win, just without
+    // the bonus for surviving it.
+    fn win(&mut self, survived: bool) {
+        if let Some(roll) = self.roll {
+            self.grader.roll_bonus(roll.bonus(survived));
+        }
+        self.grader.finish(survived);
+        self.state = State::Victory;
+    }
+    pub fn update(&mut self) {
+        self.frame += 1;
+        if self.state == State::Loss || self.state == State::Victory {
+            return;
+        }
+        self.timer.tick();
+        self.stage.tick(self.timer.frames());
+        self.board.tick();
+        self.step();
+        if let Some(roll) = self.roll.as_mut().filter(|roll| roll.rolling()) {
+            if self.state == State::Loss {
+                self.win(false);
+            } else if roll.tick() {
+                self.win(true);
+            }
+        }
+    }
+    fn step(&mut self) {
+        // Progress through inter-piece state machine; keep this in this order
+        // so that 0-frame Are and Clear phases work correctly.
+        if self.state == State::Start {
+            self.state = State::Are(0);
+            self.are_frames = self.stage.get_are_frames();
+            for _ in 0..self.rules.preview() {
+                self.next.push_back(self.rand.get_piece());
+            }
+        }
+        if let (State::RollStart(_), Some(roll)) = (self.state, self.roll.as_mut()) {
+            if roll.wait() {
+                self.state = State::Are(0);
+                self.are_frames = self.stage.get_are_frames();
+            } else {
+                self.state = State::RollStart(roll.delay());
+            }
+        }
+        if let State::Clear(n) = self.state {
+            if n >= self.stage.get_clear_frames() {
+                self.state = State::Are(0);
+                self.are_frames = self.stage.get_line_are_frames();
+            } else {
+                self.state = State::Clear(n + 1);
+            }
+        }
+        if let State::Are(n) = self.state {
+            if n >= self.are_frames {
+                self.spawn();
+            } else {
+                self.state = State::Are(n + 1);
+            }
+        }
+
+        // Handle DAS during ARE/Line-clear
+        let das = self
+            .handling
+            .das
+            .unwrap_or_else(|| self.stage.get_das_frames());
+        let arr = self
+            .handling
+            .arr
+            .unwrap_or_else(|| self.stage.get_arr_frames());
+        if self.state != State::Falling {
+            self.keys.left.service(das, arr);
+            self.keys.right.service(das, arr);
+            // Without initial hold, a hold pressed between pieces is dropped
+            // rather than saved for the next one.
+            if !self.rules.initial_hold {
+                self.keys.hold.service();
+            }
+            return;
+        }
+        self.grader.tick();
+
+        // Input
+        if self.keys.hold.service() && self.can_hold() {
+            self.hold();
+            if self.state == State::Loss {
+                return;
+            }
+        }
+        let shifts = self.keys.left.service(das, arr);
+        self.shift(shifts, BoardState::shift_left);
+        let shifts = self.keys.right.service(das, arr);
+        self.shift(shifts, BoardState::shift_right);
+        // Both directions on the same frame make a single half turn, kicked
+        // as one, rather than two quarter turns one after the other.
+        let r_left = self.keys.r_left.service();
+        let r_right = self.keys.r_right.service();
+        let r_180 = self.keys.r_180.service();
+        if r_180 || (r_left && r_right) {
+            let rotated = self.board.flip_both();
+            self.rotated(rotated);
+        } else if r_left {
+            let rotated = self.board.flip_left();
+            self.rotated(rotated);
+        } else if r_right {
+            let rotated = self.board.flip_right();
+            self.rotated(rotated);
+        }
+        if self
+            .keys
+            .sonic_drop
+            .service(DEFAULT_DAS_FRAMES, DEFAULT_ARR_FRAMES)
+            > 0
+        {
+            let mut rows = 0;
+            while self.board.fall() {
+                rows += 1;
+            }
+            self.score.sonic_drop(rows);
+        }
+        if self.keys.fast_drop.service() {
+            self.score.soft_drop();
+            if !self.board.fall() {
+                self.lock();
+                return;
+            }
+        }
+
+        // Fall
+        let stuck = self.board.stuck();
+        self.lock_delay
+            .update(stuck, self.board.current().position.1);
+        if !stuck {
+            self.gravity_count += self.stage.get_gravity();
+            while self.gravity_count >= 256 {
+                self.board.fall();
+                self.gravity_count -= 256;
+            }
+        }
+
+        // Lock
+        if self.board.stuck() {
+            self.gravity_count = 0;
+            if self.lock_delay.expired(self.stage.get_lock_frames()) {
+                self.lock();
+            }
+        }
+    }
+
+    pub fn input(&mut self, action: Action, press: bool) {
+        let over = self.state == State::Loss || self.state == State::Victory;
+        if press && !over && !self.keys.held(action) {
+            self.key_presses += 1;
+        }
+        self.keys.trigger(action, press);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::{SprintDifficulty, SPRINT_LINES};
+    use crate::grade::Grading;
+
+    // Deals the same pieces over and over.
+    struct FixedRandomizer {
+        pieces: Vec<Tetrhombino>,
+        dealt: usize,
+    }
+
+    impl Randomizer for FixedRandomizer {
+        fn get_piece(&mut self) -> Tetrhombino {
+            self.dealt += 1;
+            self.pieces[(self.dealt - 1) % self.pieces.len()]
+        }
+        fn seed(&self) -> u64 {
+            0
+        }
+    }
+
+    fn fixed_game(pieces: &[Tetrhombino], rules: Rules) -> Game<FixedRandomizer> {
+        fixed_game_with(pieces, NormalDifficulty::new(), rules)
+    }
+
+    fn fixed_game_with<D: DifficultyCurve>(
+        pieces: &[Tetrhombino],
+        stage: D,
+        rules: Rules,
+    ) -> Game<FixedRandomizer, D> {
+        let rand = FixedRandomizer {
+            pieces: pieces.to_vec(),
+            dealt: 0,
+        };
+        Game::with_mode(rand, stage, rules)
+    }
+
+    // Runs until the next piece is in play.
+    fn until_falling<R: Randomizer, D: DifficultyCurve>(game: &mut Game<R, D>) {
+        game.update();
+        while game.state() != State::Falling {
+            game.update();
+        }
+    }
+
+    fn current<R: Randomizer, D: DifficultyCurve>(game: &Game<R, D>) -> (Tetrhombino, Orientation) {
+        let piece = game.board().current();
+        (piece.tetrhombino, piece.orientation)
+    }
+
+    // Sonic drops the current piece and locks it.
+    fn drop_piece<R: Randomizer, D: DifficultyCurve>(game: &mut Game<R, D>) {
+        game.input(Action::SonicDrop, true);
+        game.input(Action::FastDrop, true);
+        game.update();
+        game.input(Action::SonicDrop, false);
+        game.input(Action::FastDrop, false);
+        assert!(game.state() != State::Falling);
+    }
+
+    // Presses `actions` while the first piece falls, and keeps them held
+    // until the second one spawns.
+    fn spawn_holding(actions: &[Action]) -> Game<FixedRandomizer> {
+        let mut game = fixed_game(&[Tetrhombino::T, Tetrhombino::L], Rules::classic());
+        until_falling(&mut game);
+        for &action in actions {
+            game.input(action, true);
+        }
+        drop_piece(&mut game);
+        until_falling(&mut game);
+        game
+    }
+
+    // Runs until the game ends, with nobody at the controls.
+    fn play_out<R: Randomizer, D: DifficultyCurve>(game: &mut Game<R, D>) -> GameResult {
+        for _ in 0..100_000 {
+            if let Some(result) = game.result() {
+                return result;
+            }
+            game.update();
+        }
+        panic!("game never ended");
+    }
+
+    #[test]
+    fn spawns_unrotated_without_irs() {
+        let game = spawn_holding(&[]);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Start));
+    }
+
+    #[test]
+    fn irs_rotates_on_spawn() {
+        let game = spawn_holding(&[Action::RotateRight]);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Right));
+        let game = spawn_holding(&[Action::RotateLeft]);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Left));
+    }
+
+    #[test]
+    fn irs_half_turns() {
+        let game = spawn_holding(&[Action::Rotate180]);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Both));
+        let game = spawn_holding(&[Action::RotateLeft, Action::RotateRight]);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Both));
+    }
+
+    #[test]
+    fn irs_does_not_rotate_again_on_the_first_frame() {
+        // Pressed during ARE, so the press is still waiting to be handled.
+        let mut game = fixed_game(&[Tetrhombino::T], Rules::classic());
+        game.update();
+        game.input(Action::RotateRight, true);
+        until_falling(&mut game);
+        assert_eq!(current(&game), (Tetrhombino::T, Orientation::Right));
+        game.update();
+        assert_eq!(current(&game), (Tetrhombino::T, Orientation::Right));
+        game.input(Action::RotateRight, false);
+        game.input(Action::RotateRight, true);
+        game.update();
+        assert_eq!(current(&game), (Tetrhombino::T, Orientation::Start));
+    }
+
+    fn hold_rules(initial_hold: bool) -> Rules {
+        Rules {
+            hold: true,
+            initial_hold,
+            ..Rules::classic()
+        }
+    }
+
+    fn press<R: Randomizer, D: DifficultyCurve>(game: &mut Game<R, D>, action: Action) {
+        game.input(action, true);
+        game.update();
+        game.input(action, false);
+    }
+
+    const PIECES: [Tetrhombino; 3] = [Tetrhombino::T, Tetrhombino::L, Tetrhombino::J];
+
+    #[test]
+    fn hold_swaps_once_per_piece() {
+        let mut game = fixed_game(&PIECES, hold_rules(false));
+        until_falling(&mut game);
+        press(&mut game, Action::Hold);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Start));
+        assert_eq!(game.held(), Some(Tetrhombino::T));
+        assert!(!game.can_hold());
+        press(&mut game, Action::Hold);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Start));
+        assert_eq!(game.held(), Some(Tetrhombino::T));
+
+        drop_piece(&mut game);
+        until_falling(&mut game);
+        assert_eq!(current(&game).0, Tetrhombino::J);
+        assert!(game.can_hold());
+        press(&mut game, Action::Hold);
+        assert_eq!(current(&game).0, Tetrhombino::T);
+        assert_eq!(game.held(), Some(Tetrhombino::J));
+    }
+
+    #[test]
+    fn hold_needs_the_rule() {
+        let mut game = fixed_game(&PIECES, Rules::classic());
+        until_falling(&mut game);
+        assert!(!game.can_hold());
+        press(&mut game, Action::Hold);
+        assert_eq!(current(&game).0, Tetrhombino::T);
+        assert_eq!(game.held(), None);
+    }
+
+    #[test]
+    fn ihs_holds_on_spawn() {
+        let mut game = fixed_game(&PIECES, hold_rules(true));
+        game.update();
+        game.input(Action::Hold, true);
+        until_falling(&mut game);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Start));
+        assert_eq!(game.held(), Some(Tetrhombino::T));
+        assert!(!game.can_hold());
+    }
+
+    #[test]
+    fn ihs_and_irs_together() {
+        let mut game = fixed_game(&PIECES, hold_rules(true));
+        game.update();
+        game.input(Action::Hold, true);
+        game.input(Action::RotateRight, true);
+        until_falling(&mut game);
+        assert_eq!(current(&game), (Tetrhombino::L, Orientation::Right));
+        assert_eq!(game.held(), Some(Tetrhombino::T));
+    }
+
+    #[test]
+    fn without_ihs_holds_between_pieces_are_dropped() {
+        let mut game = fixed_game(&PIECES, hold_rules(false));
+        game.update();
+        game.input(Action::Hold, true);
+        until_falling(&mut game);
+        assert_eq!(current(&game), (Tetrhombino::T, Orientation::Start));
+        assert_eq!(game.held(), None);
+        // Still held down, but the press has already been thrown away.
+        game.update();
+        assert_eq!(current(&game).0, Tetrhombino::T);
+        assert_eq!(game.held(), None);
+        game.input(Action::Hold, false);
+        press(&mut game, Action::Hold);
+        assert_eq!(current(&game).0, Tetrhombino::L);
+        assert_eq!(game.held(), Some(Tetrhombino::T));
+    }
+
+    // Stands an I up as it spawns and drops it into `column`.
+    fn drop_upright_i<D: DifficultyCurve>(game: &mut Game<FixedRandomizer, D>, column: i8) {
+        game.input(Action::RotateRight, true);
+        until_falling(game);
+        game.input(Action::RotateRight, false);
+        assert_eq!(current(game), (Tetrhombino::I, Orientation::Right));
+        let spawn_column = START_POSITION.0 + 1;
+        let action = if column < spawn_column {
+            Action::Left
+        } else {
+            Action::Right
+        };
+        for _ in 0..(column - spawn_column).abs() {
+            press(game, action);
+        }
+        drop_piece(game);
+    }
+
+    #[test]
+    fn sprint_rules_have_nothing_to_play_for_but_time() {
+        let rules = Rules::sprint();
+        assert_eq!(rules.grading, Grading::Off);
+        assert_eq!(rules.credit_roll, CreditRoll::Off);
+    }
+
+    #[test]
+    fn sprint_ends_at_sprint_lines() {
+        let mut game = fixed_game_with(&[Tetrhombino::I], SprintDifficulty::new(), Rules::sprint());
+        // Every ten upright Is clear four lines.
+        let pieces = SPRINT_LINES / 4 * 10;
+        for piece in 0..pieces {
+            assert_eq!(game.result(), None);
+            drop_upright_i(&mut game, (piece % 10) as i8);
+        }
+        let result = game.result().unwrap();
+        assert!(result.victory);
+        assert_eq!(result.lines, SPRINT_LINES);
+        assert_eq!(result.pieces, pieces);
+        assert_eq!(result.grade, None);
+        assert_eq!(result.splits.len(), 4);
+        assert_eq!(result.splits.iter().sum::<u64>(), result.time);
+        assert_eq!(game.state(), State::Victory);
+        assert_eq!(game.roll_frames_left(), None);
+    }
+
+    #[test]
+    fn losing_keeps_the_last_split() {
+        let result = play_out(&mut Game::new(0));
+        assert!(!result.victory);
+        assert!(!result.splits.is_empty());
+        assert_eq!(result.splits.iter().sum::<u64>(), result.time);
+    }
+}
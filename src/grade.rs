@@ -0,0 +1,352 @@
+// TGM-style grades, from 9 up through 1 and S1 to S9, with Grand Master on
+// top.  There are two ways to earn them:
+//
+// - TGM1 goes by score alone.
+// - TGM2 keeps a hidden internal grade, earned by filling up a pool of grade
+//   points with line clears.  The pool slowly drains while the player isn't
+//   clearing lines.
+//
+// Either way, lines cleared during the credit roll earn a bonus on top, and
+// GM is only awarded at the end of the game, only to players who were fast
+// enough and scoring well enough at each checkpoint and survived the roll.
+
+use crate::game::FRAMES_PER_SECOND;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Grading {
+    Off,
+    Score,  // TGM1
+    Points, // TGM2
+}
+
+impl Grading {
+    pub const ALL: [Grading; 3] = [Grading::Off, Grading::Score, Grading::Points];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Grading::Off => "off",
+            Grading::Score => "score",
+            Grading::Points => "points",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Grading> {
+        Grading::ALL.iter().cloned().find(|g| g.name() == name)
+    }
+}
+
+// 0 is grade 9, 8 is grade 1, 9 to 17 are S1 to S9, and 18 is GM.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Grade(usize);
+
+impl Grade {
+    pub const GM: Grade = Grade(18);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+    pub fn name(self) -> String {
+        match self.0 {
+            0..=8 => (9 - self.0).to_string(),
+            9..=17 => format!("S{}", self.0 - 8),
+            _ => "GM".to_string(),
+        }
+    }
+}
+
+// The score needed for each grade below GM.
+const SCORE_GRADES: [usize; 18] = [
+    0, 400, 800, 1400, 2000, 3500, 5500, 8000, 12000, 16000, 22000, 30000, 40000, 52000, 66000,
+    82000, 100000, 120000,
+];
+
+const MAX_INTERNAL_GRADE: usize = 31;
+const GRADE_POINTS_PER_GRADE: usize = 100;
+
+// Per internal grade: frames per point of decay, and points for clearing
+// 1, 2, 3 and 4 lines.
+#[rustfmt::skip]
+const INTERNAL_GRADES: [(usize, [usize; 4]); MAX_INTERNAL_GRADE + 1] = [
+    (125, [10, 20, 40, 50]),
+    (80, [10, 20, 30, 40]), (80, [10, 20, 30, 40]),
+    (50, [10, 15, 30, 40]),
+    (45, [10, 15, 20, 40]),
+    (45, [5, 15, 20, 30]),
+    (45, [5, 10, 20, 30]),
+    (40, [5, 10, 15, 30]), (40, [5, 10, 15, 30]), (40, [5, 10, 15, 30]),
+    (40, [2, 12, 13, 30]),
+    (35, [2, 12, 13, 30]), (35, [2, 12, 13, 30]), (35, [2, 12, 13, 30]), (35, [2, 12, 13, 30]),
+    (30, [2, 12, 13, 30]), (30, [2, 12, 13, 30]), (30, [2, 12, 13, 30]), (30, [2, 12, 13, 30]),
+    (30, [2, 12, 13, 30]),
+    (25, [2, 12, 13, 30]), (25, [2, 12, 13, 30]), (25, [2, 12, 13, 30]), (25, [2, 12, 13, 30]),
+    (25, [2, 12, 13, 30]),
+    (20, [2, 12, 13, 30]), (20, [2, 12, 13, 30]), (20, [2, 12, 13, 30]), (20, [2, 12, 13, 30]),
+    (20, [2, 12, 13, 30]),
+    (15, [2, 12, 13, 30]),
+    (10, [2, 12, 13, 30]),
+];
+
+// The displayed grade for each internal grade.
+const INTERNAL_TO_GRADE: [usize; MAX_INTERNAL_GRADE + 1] = [
+    0, 1, 2, 3, 4, 5, 5, 6, 6, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 11, 12, 12, 12, 13, 13, 14, 14, 15,
+    15, 16, 16, 17,
+];
+
+// Grade points are multiplied by this, in tenths, for each clear in a row
+// (capped at 10) and number of lines.
+#[rustfmt::skip]
+const COMBO_MULTIPLIER: [[usize; 4]; 10] = [
+    [10, 10, 10, 10],
+    [10, 12, 14, 15],
+    [10, 12, 15, 18],
+    [10, 14, 16, 20],
+    [10, 14, 17, 22],
+    [10, 14, 18, 23],
+    [10, 14, 19, 24],
+    [10, 15, 20, 25],
+    [10, 15, 21, 26],
+    [10, 20, 25, 30],
+];
+
+// To stay in the running for GM, reach each level with at least this score
+// within this many frames.
+const GM_CHECKPOINTS: [(usize, usize, u64); 3] = [
+    (300, 12000, (4 * 60 + 15) * FRAMES_PER_SECOND),
+    (500, 40000, (7 * 60 + 30) * FRAMES_PER_SECOND),
+    (999, 126000, (13 * 60 + 30) * FRAMES_PER_SECOND),
+];
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Grader {
+    grading: Grading,
+    grade: Grade,
+    internal_grade: usize,
+    grade_points: usize,
+    decay_frames: usize,
+    combo: usize,       // clears in a row
+    checkpoints: usize, // GM checkpoints passed so far
+    gm_failed: bool,
+    roll_bonus: usize, // in hundredths of a grade
+}
+
+impl Grader {
+    pub fn new(grading: Grading) -> Self {
+        Grader {
+            grading,
+            grade: Grade(0),
+            internal_grade: 0,
+            grade_points: 0,
+            decay_frames: 0,
+            combo: 0,
+            checkpoints: 0,
+            gm_failed: false,
+            roll_bonus: 0,
+        }
+    }
+    pub fn grade(&self) -> Option<Grade> {
+        match self.grading {
+            Grading::Off => None,
+            _ => Some(self.grade),
+        }
+    }
+    // Call once a frame while a piece is in play.  Grade points only drain
+    // between combos.
+    pub fn tick(&mut self) {
+        if self.grading != Grading::Points || self.combo > 0 {
+            return;
+        }
+        self.decay_frames += 1;
+        if self.decay_frames >= INTERNAL_GRADES[self.internal_grade].0 {
+            self.decay_frames = 0;
+            self.grade_points = self.grade_points.saturating_sub(1);
+        }
+    }
+    // Call every time a piece locks, with the level before any lines were
+    // cleared and the score after.
+    pub fn lock(&mut self, level: usize, lines: usize, score: usize) {
+        if lines == 0 {
+            self.combo = 0;
+            return;
+        }
+        self.combo += 1;
+        match self.grading {
+            Grading::Off => {}
+            Grading::Score => {
+                let grade = SCORE_GRADES.iter().filter(|&&s| score >= s).count() - 1;
+                self.grade = self.grade.max(Grade(grade));
+            }
+            Grading::Points => {
+                let lines = lines.min(4) - 1;
+                let base = INTERNAL_GRADES[self.internal_grade].1[lines];
+                let combo = COMBO_MULTIPLIER[self.combo.min(10) - 1][lines];
+                let points = (base * combo).div_ceil(10) * (1 + level / 250);
+                self.grade_points += points;
+                if self.grade_points >= GRADE_POINTS_PER_GRADE {
+                    self.grade_points = 0;
+                    self.decay_frames = 0;
+                    self.internal_grade = (self.internal_grade + 1).min(MAX_INTERNAL_GRADE);
+                }
+                self.grade = self
+                    .grade
+                    .max(Grade(INTERNAL_TO_GRADE[self.internal_grade]));
+            }
+        }
+    }
+    // Call whenever the level changes.
+    pub fn set_level(&mut self, level: usize, score: usize, frames: u64) {
+        for &(checkpoint, min_score, max_frames) in GM_CHECKPOINTS[self.checkpoints..].iter() {
+            if level < checkpoint {
+                break;
+            }
+            if score < min_score || frames > max_frames {
+                self.gm_failed = true;
+            }
+            self.checkpoints += 1;
+        }
+    }
+    pub fn roll_bonus(&mut self, hundredths: usize) {
+        self.roll_bonus += hundredths;
+    }
+    // Call when the game is won, with whether the player survived the credit
+    // roll, if there was one.
+    pub fn finish(&mut self, survived: bool) {
+        let top = Grade::GM.0 - 1;
+        self.grade = Grade((self.grade.0 + self.roll_bonus / 100).min(top));
+        let passed = self.checkpoints == GM_CHECKPOINTS.len() && !self.gm_failed;
+        if survived && passed && self.grade.0 == top {
+            self.grade = Grade::GM;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_grades_go_up_at_the_threshold() {
+        let mut grader = Grader::new(Grading::Score);
+        grader.lock(0, 1, 399);
+        assert_eq!(grader.grade(), Some(Grade(0)));
+        grader.lock(0, 1, 400);
+        assert_eq!(grader.grade().unwrap().name(), "8");
+        grader.lock(0, 1, 119_999);
+        assert_eq!(grader.grade().unwrap().name(), "S8");
+        grader.lock(0, 1, 120_000);
+        assert_eq!(grader.grade().unwrap().name(), "S9");
+    }
+
+    #[test]
+    fn grade_points_decay_between_combos() {
+        let mut grader = Grader::new(Grading::Points);
+        grader.lock(0, 1, 0);
+        assert_eq!(grader.grade_points, 10);
+        grader.lock(0, 0, 0);
+        for _ in 0..124 {
+            grader.tick();
+        }
+        assert_eq!(grader.grade_points, 10);
+        grader.tick();
+        assert_eq!(grader.grade_points, 9);
+        for _ in 0..125 * 9 {
+            grader.tick();
+        }
+        assert_eq!(grader.grade_points, 0);
+        grader.tick();
+        assert_eq!(grader.grade_points, 0);
+    }
+
+    #[test]
+    fn grade_points_do_not_decay_during_a_combo() {
+        let mut grader = Grader::new(Grading::Points);
+        grader.lock(0, 1, 0);
+        for _ in 0..1000 {
+            grader.tick();
+        }
+        assert_eq!(grader.grade_points, 10);
+    }
+
+    #[test]
+    fn combos_multiply_grade_points() {
+        let mut grader = Grader::new(Grading::Points);
+        grader.lock(0, 2, 0);
+        assert_eq!(grader.grade_points, 20);
+        grader.lock(0, 2, 0);
+        assert_eq!(grader.grade_points, 20 + 24);
+        grader.lock(0, 1, 0);
+        assert_eq!(grader.grade_points, 20 + 24 + 10);
+        grader.lock(0, 3, 0);
+        // 40 * 1.6 would take the pool past 100, which ranks up and empties it.
+        assert_eq!(grader.internal_grade, 1);
+        assert_eq!(grader.grade_points, 0);
+        assert_eq!(grader.grade().unwrap().name(), "8");
+    }
+
+    #[test]
+    fn grade_points_double_from_level_250() {
+        let mut grader = Grader::new(Grading::Points);
+        grader.lock(250, 1, 0);
+        assert_eq!(grader.grade_points, 20);
+    }
+
+    // Reaches S9 by score and the given checkpoints on time.
+    fn top_grader(checkpoints: &[(usize, usize, u64)]) -> Grader {
+        let mut grader = Grader::new(Grading::Score);
+        grader.lock(0, 4, 126_000);
+        for &(level, score, frames) in checkpoints {
+            grader.set_level(level, score, frames);
+        }
+        grader
+    }
+
+    #[test]
+    fn gm_needs_every_checkpoint() {
+        let on_time = [
+            (300, 12_000, GM_CHECKPOINTS[0].2),
+            (500, 40_000, GM_CHECKPOINTS[1].2),
+            (999, 126_000, GM_CHECKPOINTS[2].2),
+        ];
+        let mut grader = top_grader(&on_time);
+        grader.finish(true);
+        assert_eq!(grader.grade(), Some(Grade::GM));
+
+        let mut grader = top_grader(&on_time);
+        grader.finish(false);
+        assert_eq!(grader.grade().unwrap().name(), "S9");
+
+        let mut grader = top_grader(&on_time[..2]);
+        grader.finish(true);
+        assert_eq!(grader.grade().unwrap().name(), "S9");
+    }
+
+    #[test]
+    fn gm_fails_at_a_missed_checkpoint() {
+        let low_score = [
+            (300, 11_999, GM_CHECKPOINTS[0].2),
+            (500, 40_000, GM_CHECKPOINTS[1].2),
+            (999, 126_000, GM_CHECKPOINTS[2].2),
+        ];
+        let mut grader = top_grader(&low_score);
+        grader.finish(true);
+        assert_eq!(grader.grade().unwrap().name(), "S9");
+
+        let too_slow = [
+            (300, 12_000, GM_CHECKPOINTS[0].2),
+            (500, 40_000, GM_CHECKPOINTS[1].2 + 1),
+            (999, 126_000, GM_CHECKPOINTS[2].2),
+        ];
+        let mut grader = top_grader(&too_slow);
+        grader.finish(true);
+        assert_eq!(grader.grade().unwrap().name(), "S9");
+    }
+
+    #[test]
+    fn checkpoints_are_only_judged_once_reached() {
+        let mut grader = Grader::new(Grading::Score);
+        grader.set_level(299, 0, 0);
+        assert_eq!(grader.checkpoints, 0);
+        assert!(!grader.gm_failed);
+        grader.set_level(300, 0, 0);
+        assert_eq!(grader.checkpoints, 1);
+        assert!(grader.gm_failed);
+    }
+}
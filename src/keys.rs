@@ -0,0 +1,331 @@
+pub const DEFAULT_DAS_FRAMES: usize = 14;
+pub const DEFAULT_ARR_FRAMES: usize = 1;
+
+// More shifts than the board is wide: what a 0-frame ARR asks for.
+pub const INSTANT_REPEAT: usize = usize::MAX;
+
+// How a player likes held movement keys to repeat.  This is up to the player
+// rather than the mode, so it lives outside `Rules`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Handling {
+    pub das: Option<usize>, // frames before repeating; `None` follows the difficulty curve
+    pub arr: Option<usize>, // frames between repeats, 0 for instant; `None` as above
+    pub das_cut: usize,     // frames repeating is held off after a spawn or rotation
+}
+
+impl Handling {
+    pub fn new() -> Self {
+        Handling {
+            das: None,
+            arr: None,
+            das_cut: 0,
+        }
+    }
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The abstract buttons the engine understands.  Frontends map their physical
+// keys onto these.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Action {
+    Left,
+    Right,
+    SonicDrop,
+    FastDrop,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    Hold,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Left,
+        Action::Right,
+        Action::SonicDrop,
+        Action::FastDrop,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Rotate180,
+        Action::Hold,
+    ];
+
+    // Stable names, for use in files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::SonicDrop => "sonic_drop",
+            Action::FastDrop => "fast_drop",
+            Action::RotateLeft => "rotate_left",
+            Action::RotateRight => "rotate_right",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|a| a.name() == name)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SingleKey {
+    triggered: bool,
+    needs_service: bool,
+}
+
+impl SingleKey {
+    pub fn new() -> Self {
+        SingleKey {
+            triggered: false,
+            needs_service: false,
+        }
+    }
+    pub fn trigger(&mut self, press: bool) {
+        if press {
+            if !self.triggered {
+                self.triggered = true;
+                self.needs_service = true;
+            }
+        } else {
+            self.triggered = false;
+        }
+    }
+    pub fn service(&mut self) -> bool {
+        self.triggered && std::mem::replace(&mut self.needs_service, false)
+    }
+    pub fn held(&self) -> bool {
+        self.triggered
+    }
+}
+
+impl Default for SingleKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct MultiKey {
+    state: SingleKey,
+    hold_frames: usize,
+    cut_frames: usize, // repeats are suppressed until this runs out
+}
+
+impl MultiKey {
+    pub fn new() -> Self {
+        MultiKey {
+            state: SingleKey::new(),
+            hold_frames: 0,
+            cut_frames: 0,
+        }
+    }
+    pub fn trigger(&mut self, press: bool) {
+        self.state.trigger(press);
+        if !press {
+            self.hold_frames = 0;
+        }
+    }
+    // Returns how many times to act this frame: once on the initial press,
+    // then after `das_frames` once every `arr_frames`, or `INSTANT_REPEAT`
+    // times at once if `arr_frames` is 0.
+    pub fn service(&mut self, das_frames: usize, arr_frames: usize) -> usize {
+        let cut = self.cut_frames > 0;
+        self.cut_frames = self.cut_frames.saturating_sub(1);
+        if !self.state.triggered {
+            return 0;
+        }
+        self.hold_frames += 1;
+        if self.state.service() {
+            1
+        } else if cut || self.hold_frames < das_frames {
+            0
+        } else if arr_frames == 0 {
+            INSTANT_REPEAT
+        } else if (self.hold_frames - das_frames) % arr_frames == 0 {
+            1
+        } else {
+            0
+        }
+    }
+    // Holds off repeating for `frames` frames, without losing the charge.
+    pub fn cut(&mut self, frames: usize) {
+        self.cut_frames = frames;
+    }
+    pub fn held(&self) -> bool {
+        self.state.held()
+    }
+}
+
+impl Default for MultiKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ContinuousKey {
+    pressed: bool,
+}
+
+impl ContinuousKey {
+    pub fn new() -> Self {
+        ContinuousKey { pressed: false }
+    }
+    pub fn trigger(&mut self, press: bool) {
+        self.pressed = press;
+    }
+    pub fn service(&mut self) -> bool {
+        self.pressed
+    }
+    pub fn held(&self) -> bool {
+        self.pressed
+    }
+}
+
+impl Default for ContinuousKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct KeyState {
+    pub left: MultiKey,
+    pub right: MultiKey,
+    pub sonic_drop: MultiKey,     // Drop all the way but do not lock
+    pub fast_drop: ContinuousKey, // Lock if fallen; otherwise drop a frame
+    pub r_left: SingleKey,
+    pub r_right: SingleKey,
+    pub r_180: SingleKey,
+    pub hold: SingleKey,
+}
+
+impl KeyState {
+    pub fn new() -> KeyState {
+        KeyState {
+            left: MultiKey::new(),
+            right: MultiKey::new(),
+            fast_drop: ContinuousKey::new(),
+            sonic_drop: MultiKey::new(),
+            r_left: SingleKey::new(),
+            r_right: SingleKey::new(),
+            r_180: SingleKey::new(),
+            hold: SingleKey::new(),
+        }
+    }
+    // Pauses auto-repeat on both movement keys.
+    pub fn cut_das(&mut self, frames: usize) {
+        self.left.cut(frames);
+        self.right.cut(frames);
+    }
+    pub fn held(&self, action: Action) -> bool {
+        match action {
+            Action::Left => self.left.held(),
+            Action::Right => self.right.held(),
+            Action::SonicDrop => self.sonic_drop.held(),
+            Action::FastDrop => self.fast_drop.held(),
+            Action::RotateLeft => self.r_left.held(),
+            Action::RotateRight => self.r_right.held(),
+            Action::Rotate180 => self.r_180.held(),
+            Action::Hold => self.hold.held(),
+        }
+    }
+    pub fn trigger(&mut self, action: Action, press: bool) {
+        match action {
+            Action::Left => self.left.trigger(press),
+            Action::Right => self.right.trigger(press),
+            Action::SonicDrop => self.sonic_drop.trigger(press),
+            Action::FastDrop => self.fast_drop.trigger(press),
+            Action::RotateLeft => self.r_left.trigger(press),
+            Action::RotateRight => self.r_right.trigger(press),
+            Action::Rotate180 => self.r_180.trigger(press),
+            Action::Hold => self.hold.trigger(press),
+        }
+    }
+}
+
+impl Default for KeyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What `service` returns on each of the next `frames` frames.
+    fn shifts(key: &mut MultiKey, das: usize, arr: usize, frames: usize) -> Vec<usize> {
+        (0..frames).map(|_| key.service(das, arr)).collect()
+    }
+
+    #[test]
+    fn repeats_every_frame_after_das() {
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 3, 1, 6), vec![1, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn repeats_every_arr_frames_after_das() {
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 3, 2, 8), vec![1, 0, 1, 0, 1, 0, 1, 0]);
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 14, 3, 20)[13..], [1, 0, 0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn zero_arr_shifts_all_the_way_at_once() {
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(
+            shifts(&mut key, 3, 0, 4),
+            vec![1, 0, INSTANT_REPEAT, INSTANT_REPEAT]
+        );
+    }
+
+    #[test]
+    fn releasing_loses_the_charge() {
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 3, 1, 3), vec![1, 0, 1]);
+        key.trigger(false);
+        assert_eq!(shifts(&mut key, 3, 1, 2), vec![0, 0]);
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 3, 1, 3), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn das_cut_holds_off_repeats_but_keeps_the_charge() {
+        let mut key = MultiKey::new();
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 3, 1, 4), vec![1, 0, 1, 1]);
+        key.cut(2);
+        assert_eq!(shifts(&mut key, 3, 1, 3), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn das_cut_does_not_swallow_a_new_press() {
+        let mut key = MultiKey::new();
+        key.cut(2);
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 1, 1, 3), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn das_cut_runs_out_while_the_key_is_up() {
+        let mut key = MultiKey::new();
+        key.cut(2);
+        assert_eq!(shifts(&mut key, 1, 1, 2), vec![0, 0]);
+        key.trigger(true);
+        assert_eq!(shifts(&mut key, 1, 1, 3), vec![1, 1, 1]);
+    }
+}
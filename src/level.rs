@@ -0,0 +1,103 @@
+// TGM-style level counter.  The level goes up by one for every piece that
+// spawns and by one for every line cleared, but spawning a piece will not
+// advance it past the end of a section (x99), nor to the final level.  Only
+// clearing lines can do that.
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct LevelCounter {
+    level: usize,
+    max_level: usize,
+    pieces_spawned: usize,
+}
+
+pub const SECTION_LENGTH: usize = 100;
+
+impl LevelCounter {
+    pub fn new(max_level: usize) -> Self {
+        LevelCounter {
+            level: 0,
+            max_level,
+            pieces_spawned: 0,
+        }
+    }
+    pub fn level(&self) -> usize {
+        self.level
+    }
+    pub fn section(&self) -> usize {
+        self.level / SECTION_LENGTH
+    }
+    pub fn at_stop(&self) -> bool {
+        self.level % SECTION_LENGTH == SECTION_LENGTH - 1 || self.level + 1 >= self.max_level
+    }
+    // The first piece of the game does not count.
+    pub fn spawn(&mut self) {
+        if self.pieces_spawned > 0 && !self.at_stop() {
+            self.level += 1;
+        }
+        self.pieces_spawned += 1;
+    }
+    pub fn clear_lines(&mut self, lines: usize) {
+        self.level = (self.level + lines).min(self.max_level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(counter: &mut LevelCounter, pieces: usize) {
+        for _ in 0..pieces {
+            counter.spawn();
+        }
+    }
+
+    #[test]
+    fn the_first_piece_does_not_count() {
+        let mut counter = LevelCounter::new(999);
+        spawn(&mut counter, 1);
+        assert_eq!(counter.level(), 0);
+        spawn(&mut counter, 1);
+        assert_eq!(counter.level(), 1);
+    }
+
+    #[test]
+    fn pieces_stop_at_the_end_of_a_section() {
+        let mut counter = LevelCounter::new(999);
+        spawn(&mut counter, 200);
+        assert_eq!(counter.level(), 99);
+        assert_eq!(counter.section(), 0);
+        assert!(counter.at_stop());
+    }
+
+    #[test]
+    fn line_clears_cross_the_end_of_a_section() {
+        let mut counter = LevelCounter::new(999);
+        spawn(&mut counter, 100);
+        assert_eq!(counter.level(), 99);
+        counter.clear_lines(1);
+        assert_eq!(counter.level(), 100);
+        assert_eq!(counter.section(), 1);
+        spawn(&mut counter, 1);
+        assert_eq!(counter.level(), 101);
+
+        let mut counter = LevelCounter::new(999);
+        spawn(&mut counter, 98);
+        assert_eq!(counter.level(), 97);
+        counter.clear_lines(4);
+        assert_eq!(counter.level(), 101);
+    }
+
+    #[test]
+    fn pieces_stop_before_the_final_level() {
+        let mut counter = LevelCounter::new(999);
+        for _ in 0..9 {
+            spawn(&mut counter, SECTION_LENGTH);
+            counter.clear_lines(1);
+        }
+        assert_eq!(counter.level(), 900);
+        spawn(&mut counter, 200);
+        assert_eq!(counter.level(), 998);
+        counter.clear_lines(4);
+        assert_eq!(counter.level(), 999);
+    }
+}
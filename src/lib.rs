@@ -0,0 +1,42 @@
+// The rules of the game, with no dependency on any windowing or graphics
+// library.  The `rhombus-instinct` binary is a thin piston frontend on top of
+// this; bots, replay tools and tests can link it directly.
+
+pub mod bindings;
+pub mod board;
+pub mod difficulty;
+pub mod game;
+pub mod grade;
+pub mod keys;
+pub mod level;
+pub mod lock;
+pub mod mode;
+pub mod piece;
+pub mod randomizer;
+pub mod replay;
+pub mod roll;
+pub mod rotation;
+pub mod rules;
+pub mod score;
+pub mod timer;
+
+pub use crate::bindings::{Bindings, Command};
+pub use crate::board::{Board, BoardState, Visibility, BOARD_HEIGHT, BOARD_WIDTH};
+pub use crate::difficulty::{
+    DeathDifficulty, DifficultyCurve, MasterDifficulty, NormalDifficulty, ShiraseDifficulty,
+    SprintDifficulty, SPRINT_LINES, TWENTY_G,
+};
+pub use crate::game::{Game, GameResult, State, FRAMES_PER_SECOND};
+pub use crate::grade::{Grade, Grader, Grading};
+pub use crate::keys::{Action, Handling, KeyState};
+pub use crate::level::LevelCounter;
+pub use crate::lock::{LockDelay, LockReset};
+pub use crate::mode::{DynGame, Mode};
+pub use crate::piece::{Orientation, Position, Tetrhombino, TetrhombinoState};
+pub use crate::randomizer::{BagRandomizer, Randomizer, TGMRandomizer};
+pub use crate::replay::{Playback, Replay, ReplayInput};
+pub use crate::roll::{CreditRoll, Roll};
+pub use crate::rotation::{KickCondition, KickRule, RotationSystem};
+pub use crate::rules::{Rules, MAX_PREVIEW};
+pub use crate::score::Score;
+pub use crate::timer::{format_time, Sections, Timer};
@@ -0,0 +1,181 @@
+// Lock delay: how long a piece may sit on the stack before it locks, and what
+// buys it more time.
+
+pub const DEFAULT_MOVE_RESETS: usize = 15;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum LockReset {
+    // TGM: the delay starts over only when the piece is lifted off the stack,
+    // i.e. it can fall again.
+    Step,
+    // Guideline: the delay also starts over on every successful move or
+    // rotation, up to this many times.  Reaching a new lowest row earns
+    // another set of resets.
+    Move(usize),
+    // Pieces never lock on their own; for practice.
+    Infinite,
+}
+
+impl LockReset {
+    pub fn name(self) -> &'static str {
+        match self {
+            LockReset::Step => "step",
+            LockReset::Move(_) => "move",
+            LockReset::Infinite => "infinite",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<LockReset> {
+        match name {
+            "step" => Some(LockReset::Step),
+            "move" => Some(LockReset::Move(DEFAULT_MOVE_RESETS)),
+            "infinite" => Some(LockReset::Infinite),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct LockDelay {
+    reset: LockReset,
+    frames: usize, // spent on the stack since the last reset
+    resets: usize, // move resets used since reaching `lowest`
+    lowest: i8,    // lowest row the current piece has reached
+}
+
+impl LockDelay {
+    pub fn new(reset: LockReset) -> Self {
+        LockDelay {
+            reset,
+            frames: 0,
+            resets: 0,
+            lowest: i8::MAX,
+        }
+    }
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+    // Call when a new piece appears at row `y`.
+    pub fn spawn(&mut self, y: i8) {
+        self.frames = 0;
+        self.resets = 0;
+        self.lowest = y;
+    }
+    // Call after every successful shift or rotation.
+    pub fn moved(&mut self) {
+        if let LockReset::Move(limit) = self.reset {
+            if self.resets < limit {
+                self.resets += 1;
+                self.frames = 0;
+            }
+        }
+    }
+    // Call once a frame, before gravity, with whether the piece is resting on
+    // the stack and the row it is at.
+    pub fn update(&mut self, stuck: bool, y: i8) {
+        if y < self.lowest {
+            self.lowest = y;
+            if let LockReset::Move(_) = self.reset {
+                self.resets = 0;
+                self.frames = 0;
+            }
+        }
+        if stuck {
+            self.frames += 1;
+        } else if self.reset == LockReset::Step {
+            self.frames = 0;
+        }
+    }
+    pub fn expired(&self, lock_frames: usize) -> bool {
+        match self.reset {
+            LockReset::Infinite => false,
+            _ => self.frames >= lock_frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK_FRAMES: usize = 30;
+
+    // Rests the piece on the stack at row `y` for `frames` frames.
+    fn rest(lock: &mut LockDelay, y: i8, frames: usize) {
+        for _ in 0..frames {
+            lock.update(true, y);
+        }
+    }
+
+    #[test]
+    fn step_reset_starts_over_only_when_lifted() {
+        let mut lock = LockDelay::new(LockReset::Step);
+        lock.spawn(20);
+        rest(&mut lock, 20, LOCK_FRAMES - 1);
+        lock.moved();
+        assert!(!lock.expired(LOCK_FRAMES));
+        assert_eq!(lock.frames(), LOCK_FRAMES - 1);
+        lock.update(false, 20);
+        assert_eq!(lock.frames(), 0);
+        rest(&mut lock, 20, LOCK_FRAMES);
+        assert!(lock.expired(LOCK_FRAMES));
+    }
+
+    #[test]
+    fn move_reset_stops_at_the_limit() {
+        let mut lock = LockDelay::new(LockReset::Move(3));
+        lock.spawn(20);
+        for _ in 0..3 {
+            rest(&mut lock, 20, LOCK_FRAMES - 1);
+            lock.moved();
+            assert_eq!(lock.frames(), 0);
+        }
+        rest(&mut lock, 20, LOCK_FRAMES - 1);
+        lock.moved();
+        assert_eq!(lock.frames(), LOCK_FRAMES - 1);
+        rest(&mut lock, 20, 1);
+        assert!(lock.expired(LOCK_FRAMES));
+    }
+
+    #[test]
+    fn move_reset_keeps_counting_while_lifted() {
+        let mut lock = LockDelay::new(LockReset::Move(3));
+        lock.spawn(20);
+        rest(&mut lock, 20, 10);
+        lock.update(false, 20);
+        assert_eq!(lock.frames(), 10);
+    }
+
+    #[test]
+    fn new_lowest_row_refills_move_resets() {
+        let mut lock = LockDelay::new(LockReset::Move(2));
+        lock.spawn(20);
+        for _ in 0..2 {
+            rest(&mut lock, 20, 5);
+            lock.moved();
+        }
+        rest(&mut lock, 20, 5);
+        lock.moved();
+        assert_eq!(lock.frames(), 5);
+        // Dropping a row starts the delay over and allows two more resets.
+        lock.update(true, 19);
+        assert_eq!(lock.frames(), 1);
+        for _ in 0..2 {
+            rest(&mut lock, 19, 5);
+            lock.moved();
+            assert_eq!(lock.frames(), 0);
+        }
+        // Going back up doesn't.
+        rest(&mut lock, 20, 5);
+        lock.moved();
+        assert_eq!(lock.frames(), 5);
+    }
+
+    #[test]
+    fn infinite_never_expires() {
+        let mut lock = LockDelay::new(LockReset::Infinite);
+        lock.spawn(20);
+        rest(&mut lock, 20, 10 * LOCK_FRAMES);
+        assert!(!lock.expired(LOCK_FRAMES));
+        assert!(!lock.expired(0));
+    }
+}
@@ -1,868 +1,646 @@
-use rand::Rng;
-
-// The board's bottom slants upper right to lower left.  So, the S and L have
-// obtuse angles, while the Z and J have acute angles.
-
-// I
-// I OO  SS ZZ  LLL JJJ TTT
-// I OO SS   ZZ L     J  T
-// I
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Tetrhombino {
-    I,
-    O,
-    S,
-    Z,
-    L,
-    J,
-    T,
+use std::collections::{HashMap, HashSet};
+
+use rhombus_instinct::{
+    format_time, Action, Bindings, Command, CreditRoll, DifficultyCurve, Game, GameResult, Grade,
+    Grading, Handling, LockReset, Mode, Orientation, Playback, Position, Randomizer, Replay,
+    RotationSystem, Rules, State, Tetrhombino, TetrhombinoState, BOARD_HEIGHT, BOARD_WIDTH,
+    FRAMES_PER_SECOND,
+};
+
+fn draw_rhomb(
+    (x, y): Position,
+    color: [f32; 4],
+    ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    graphics::Rectangle::new(color).draw(
+        [f64::from(x), f64::from(y), 1.0, 1.0],
+        &ctxt.draw_state,
+        ctxt.transform,
+        gl,
+    );
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Orientation {
-    Start,
-    Right,
-    Both,
-    Left,
-}
-
-impl Orientation {
-    fn flip_right(&mut self) {
-        *self = match *self {
-            Orientation::Start => Orientation::Right,
-            Orientation::Right => Orientation::Start,
-            Orientation::Both => Orientation::Left,
-            Orientation::Left => Orientation::Both,
-        }
-    }
-    fn flip_left(&mut self) {
-        *self = match *self {
-            Orientation::Start => Orientation::Left,
-            Orientation::Right => Orientation::Both,
-            Orientation::Both => Orientation::Right,
-            Orientation::Left => Orientation::Start,
-        }
+fn draw_tetrhombino(
+    rotation: &RotationSystem,
+    state: TetrhombinoState,
+    color: [f32; 4],
+    ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    for pos in rotation.occupied_places(state).iter() {
+        draw_rhomb(*pos, color, ctxt, gl);
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ReducedOrientation {
-    Start,
-    Flipped,
-}
-
-// The 'O' piece has one orientation.  Its bounding box has no center; its
-// location is defined by the '*', in the upper-left hand corner.
-
-//   *O
-//   OO
-
-// The 'I', 'S', and 'Z' pieces have two orientations: one for Start/Both, one
-// for Left/Right.  The 'I' piece's bounding box has no center; its location is
-// again defined by the '*', even when it is outside the piece.
+const SEGMENT_COLOR: [f32; 4] = [0.0, 0.7, 1.0, 1.0];
 
-//   ....  ..I.
-//   I*II  .*I.
-//   ....  ..I.
-//   ....  ..I.
+fn draw_segment(on: bool, ctxt: graphics::context::Context, gl: &mut opengl_graphics::GlGraphics) {
+    const SEGMENT: [[f64; 2]; 6] = [
+        [0.05, 0.0],
+        [0.10, 0.05],
+        [0.90, 0.05],
+        [0.95, 0.0],
+        [0.90, -0.05],
+        [0.10, -0.05],
+    ];
 
-//   ...  ..S
-//   .SS  .SS
-//   SS.  .S.
-
-//   ...  Z..
-//   ZZ.  ZZ.
-//   .ZZ  .Z.
-
-// The 'L', 'J', and 'T' pieces have four orientations, ordered here as Start,
-// Right, Both, Left.
-
-//   ...  .L.  ...  .LL
-//   LLL  .L.  ..L  .L.
-//   L..  LL.  LLL  .L.
-
-//   ...  JJ.  ...  .J.
-//   JJJ  .J.  J..  .J.
-//   ..J  .J.  JJJ  .JJ
-
-//   ...  .T.  ...  .T.
-//   TTT  TT.  .T.  .TT
-//   .T.  .T.  TTT  .T.
-
-type Position = (i8, i8);
-
-fn reduce_orientation(or: Orientation) -> ReducedOrientation {
-    match or {
-        Orientation::Start => ReducedOrientation::Start,
-        Orientation::Both => ReducedOrientation::Start,
-        Orientation::Left => ReducedOrientation::Flipped,
-        Orientation::Right => ReducedOrientation::Flipped,
+    if on {
+        graphics::polygon(SEGMENT_COLOR, &SEGMENT, ctxt.transform, gl);
     }
 }
 
-const START_POSITION: Position = (4, 20);
-
-trait Randomizer {
-    fn get_piece(&mut self) -> Tetrhombino;
-}
-
-#[derive(Debug, Copy, Clone)]
-struct TGMRandomizer {
-    history: [Tetrhombino; 4],
-    pieces_given: usize,
-}
-
-impl TGMRandomizer {
-    fn new() -> Self {
-        TGMRandomizer {
-            history: [
-                Tetrhombino::Z,
-                Tetrhombino::Z,
-                Tetrhombino::S,
-                Tetrhombino::S,
-            ],
-            pieces_given: 0,
-        }
-    }
-    fn helper(&self) -> Tetrhombino {
-        if self.pieces_given == 0 {
-            [
-                Tetrhombino::I,
-                Tetrhombino::T,
-                Tetrhombino::L,
-                Tetrhombino::J,
-            ][rand::thread_rng().gen_range(0, 4)]
-        } else {
-            [
-                Tetrhombino::O,
-                Tetrhombino::I,
-                Tetrhombino::S,
-                Tetrhombino::Z,
-                Tetrhombino::T,
-                Tetrhombino::L,
-                Tetrhombino::J,
-            ][rand::thread_rng().gen_range(0, 7)]
-        }
+//   4
+// 0   2
+//   5
+// 1   3
+//   6
+fn draw_segments(
+    segments: [bool; 7],
+    ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    use graphics::Transformed;
+    let ctxts = [
+        ctxt.trans(0.0, 1.0).rot_deg(90.0),
+        ctxt.rot_deg(90.0),
+        ctxt.trans(1.0, 1.0).rot_deg(90.0),
+        ctxt.trans(1.0, 0.0).rot_deg(90.0),
+        ctxt.trans(0.0, 2.0),
+        ctxt.trans(0.0, 1.0),
+        ctxt,
+    ];
+
+    for (on, ctxt) in segments.iter().zip(ctxts.iter()) {
+        draw_segment(*on, *ctxt, gl);
     }
 }
 
-impl Randomizer for TGMRandomizer {
-    fn get_piece(&mut self) -> Tetrhombino {
-        let mut res = self.helper();
-        for _ in 0..6 {
-            if !self.history.iter().any(|saved| *saved == res) {
-                break;
-            }
-            res = self.helper();
-        }
-        self.history[self.pieces_given % 4] = res;
-        self.pieces_given += 1;
-        res
-    }
-}
-
-type Board = [[Option<Tetrhombino>; 22]; 10];
-
-#[derive(Debug, Clone, Copy)]
-struct TetrhombinoState {
-    tetrhombino: Tetrhombino,
-    orientation: Orientation,
-    position: Position,
+fn draw_digit(digit: u8, ctxt: graphics::context::Context, gl: &mut opengl_graphics::GlGraphics) {
+    const DIGITS: [[bool; 7]; 10] = [
+        [true, true, true, true, true, false, true],
+        [false, false, true, true, false, false, false],
+        [false, true, true, false, true, true, true],
+        [false, false, true, true, true, true, true],
+        [true, false, true, true, false, true, false],
+        [true, false, false, true, true, true, true],
+        [true, true, false, true, true, true, true],
+        [false, false, true, true, true, false, false],
+        [true, true, true, true, true, true, true],
+        [true, false, true, true, true, true, true],
+    ];
+    draw_segments(DIGITS[digit as usize], ctxt, gl);
 }
 
-impl TetrhombinoState {
-    fn occupied_places(self) -> [Position; 4] {
-        let mut result = match self.tetrhombino {
-            Tetrhombino::O => [(0, 0), (1, 0), (0, -1), (1, -1)],
-            Tetrhombino::I => match reduce_orientation(self.orientation) {
-                ReducedOrientation::Start => [(2, 0), (1, 0), (0, 0), (-1, 0)],
-                ReducedOrientation::Flipped => [(1, 1), (1, 0), (1, -1), (1, -2)],
-            },
-            Tetrhombino::S => match reduce_orientation(self.orientation) {
-                ReducedOrientation::Start => [(1, 0), (0, 0), (0, -1), (-1, -1)],
-                ReducedOrientation::Flipped => [(0, -1), (0, 0), (1, 0), (1, 1)],
-            },
-            Tetrhombino::Z => match reduce_orientation(self.orientation) {
-                ReducedOrientation::Start => [(-1, 0), (0, 0), (0, -1), (1, -1)],
-                ReducedOrientation::Flipped => [(0, -1), (0, 0), (-1, 0), (-1, 1)],
-            },
-            Tetrhombino::T => match self.orientation {
-                Orientation::Start => [(0, 0), (-1, 0), (1, 0), (0, -1)],
-                Orientation::Right => [(0, 0), (0, 1), (0, -1), (-1, 0)],
-                Orientation::Left => [(0, 0), (0, 1), (0, -1), (1, 0)],
-                Orientation::Both => [(0, 0), (-1, -1), (0, -1), (1, -1)],
-            },
-            Tetrhombino::L => match self.orientation {
-                Orientation::Start => [(0, 0), (1, 0), (-1, 0), (-1, -1)],
-                Orientation::Right => [(0, 0), (0, 1), (0, -1), (-1, -1)],
-                Orientation::Left => [(0, 0), (0, -1), (0, 1), (1, 1)],
-                Orientation::Both => [(1, 0), (1, -1), (0, -1), (-1, -1)],
-            },
-            Tetrhombino::J => match self.orientation {
-                Orientation::Start => [(0, 0), (-1, 0), (1, 0), (1, -1)],
-                Orientation::Right => [(0, 0), (0, 1), (0, -1), (1, -1)],
-                Orientation::Left => [(0, 0), (0, -1), (0, 1), (-1, 1)],
-                Orientation::Both => [(-1, 0), (-1, -1), (0, -1), (1, -1)],
-            },
-        };
-        for elt in result.iter_mut() {
-            elt.0 += self.position.0;
-            elt.1 += self.position.1;
+// Grades 9 to 1 are plain digits, the S grades get an S in front, and GM is
+// as close as seven segments can get.
+fn draw_grade(
+    grade: Grade,
+    ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    const S: [bool; 7] = [true, false, false, true, true, true, true];
+    const G: [bool; 7] = [true, true, false, true, true, false, true];
+    const M: [bool; 7] = [true, true, true, true, true, false, false];
+    use graphics::Transformed;
+    let second = ctxt.trans(1.5, 0.0);
+    match grade.index() {
+        i @ 0..=8 => draw_digit(9 - i as u8, ctxt, gl),
+        i @ 9..=17 => {
+            draw_segments(S, ctxt, gl);
+            draw_digit(i as u8 - 8, second, gl);
+        }
+        _ => {
+            draw_segments(G, ctxt, gl);
+            draw_segments(M, second, gl);
         }
-        result
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct BoardState {
-    board: Board,
-    current: TetrhombinoState,
+fn draw_number(
+    mut number: usize,
+    mut ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    if number == 0 {
+        draw_digit(0, ctxt, gl);
+        return;
+    }
+    while number > 0 {
+        draw_digit((number % 10) as u8, ctxt, gl);
+        number /= 10;
+        use graphics::Transformed;
+        ctxt = ctxt.trans(-1.5, 0.0);
+    }
 }
 
-impl BoardState {
-    fn occupied(&self, pos: Position) -> bool {
-        let (x, y) = pos;
-        if x < 0 || x >= 10 || y < 0 || y >= 22 {
-            true
-        } else {
-            self.board[x as usize][y as usize] != None
-        }
-    }
-    fn center_column_conflicts(&self) -> bool {
-        let (x, y) = self.current.position;
-        self.occupied((x, y - 1)) || self.occupied((x, y)) || self.occupied((x, y + 1))
-    }
-    fn piece_conflicts(&self, state: TetrhombinoState) -> bool {
-        state
-            .occupied_places()
-            .into_iter()
-            .any(|pos| self.occupied(*pos))
-    }
-    fn current_piece_conflicts(&self) -> bool {
-        self.piece_conflicts(self.current)
-    }
-    fn kick_allowed(&self) -> bool {
-        let (x, y) = self.current.position;
-        match self.current.tetrhombino {
-            Tetrhombino::O => false,
-            Tetrhombino::I => false, // Change for TGM 3 semantics.
-            Tetrhombino::S => true,
-            Tetrhombino::Z => true,
-            Tetrhombino::T => match reduce_orientation(self.current.orientation) {
-                ReducedOrientation::Start => true,
-                ReducedOrientation::Flipped => !self.center_column_conflicts(),
-            },
-            Tetrhombino::L => match self.current.orientation {
-                Orientation::Start => true,
-                Orientation::Both => true,
-                Orientation::Right => self.occupied((x - 1, y - 1)),
-                Orientation::Left => self.occupied((x + 1, y + 1)),
-            },
-            Tetrhombino::J => match self.current.orientation {
-                Orientation::Start => true,
-                Orientation::Both => true,
-                Orientation::Right => self.occupied((x - 1, y + 1)),
-                Orientation::Left => self.occupied((x + 1, y - 1)),
-            },
-        }
-    }
-    fn finish_rotate(&mut self) -> bool {
-        if !self.current_piece_conflicts() {
-            return true;
-        }
-        if !self.kick_allowed() {
-            return false;
-        }
-        if self.shift_right() {
-            return true;
-        }
-        if self.shift_left() {
-            return true;
-        }
-        false
-    }
-    fn new() -> Self {
-        BoardState {
-            current: TetrhombinoState {
-                tetrhombino: Tetrhombino::I,
-                orientation: Orientation::Start,
-                position: (100, 100), // off the board.
-            },
-            board: [[None; 22]; 10],
-        }
-    }
-    fn stuck(&mut self) -> bool {
-        self.current.position.1 -= 1;
-        let result = self.current_piece_conflicts();
-        self.current.position.1 += 1;
-        result
-    }
-    fn fall(&mut self) -> bool {
-        self.current.position.1 -= 1;
-        if self.current_piece_conflicts() {
-            self.current.position.1 += 1;
-            false
-        } else {
-            true
-        }
-    }
-    fn get_shadow(&self) -> TetrhombinoState {
-        let mut res = self.current;
-        while !self.piece_conflicts(res) {
-            res.position.1 -= 1;
-        }
-        res.position.1 += 1;
-        res
-    }
-    fn flip_right(&mut self) -> bool {
-        self.current.orientation.flip_right();
-        if !self.finish_rotate() {
-            self.current.orientation.flip_right();
-            false
-        } else {
-            true
-        }
-    }
-    fn flip_left(&mut self) -> bool {
-        self.current.orientation.flip_left();
-        if !self.finish_rotate() {
-            self.current.orientation.flip_left();
-            false
-        } else {
-            true
-        }
-    }
-    fn shift_left(&mut self) -> bool {
-        self.current.position.0 -= 1;
-        if self.current_piece_conflicts() {
-            self.current.position.0 += 1;
-            false
-        } else {
-            true
-        }
-    }
-    fn shift_right(&mut self) -> bool {
-        self.current.position.0 += 1;
-        if self.current_piece_conflicts() {
-            self.current.position.0 -= 1;
-            false
-        } else {
-            true
-        }
-    }
-    fn lock(&mut self) {
-        for (x, y) in self.current.occupied_places().into_iter() {
-            self.board[(*x) as usize][(*y) as usize] = Some(self.current.tetrhombino);
-        }
-    }
-    fn clear(&mut self) -> usize {
-        let mut cursor: usize = 0;
-        let mut result = 0;
-        for read in 0..22 {
-            if (0..10).any(|x| self.board[x][read] == None) {
-                for x in 0..10 {
-                    self.board[x][cursor] = self.board[x][read];
-                }
-                cursor += 1;
-            } else {
-                result += 1;
+// Digits, colons and points, left to right from `ctxt` unlike `draw_number`.
+fn draw_text(
+    text: &str,
+    mut ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    use graphics::Transformed;
+    for c in text.chars() {
+        match c.to_digit(10) {
+            Some(digit) => {
+                draw_digit(digit as u8, ctxt, gl);
+                ctxt = ctxt.trans(1.5, 0.0);
             }
-        }
-        for write in cursor..22 {
-            for x in 0..10 {
-                self.board[x][write] = None;
+            None => {
+                let dots: &[f64] = if c == ':' { &[0.5, 1.5] } else { &[0.0] };
+                for y in dots.iter() {
+                    graphics::rectangle(SEGMENT_COLOR, [0.0, *y, 0.1, 0.1], ctxt.transform, gl);
+                }
+                ctxt = ctxt.trans(0.6, 0.0);
             }
         }
-        result
-    }
-    fn spawn(&mut self, new_piece: TetrhombinoState) {
-        self.current = new_piece;
     }
 }
 
-// Proper game logic:
-// - spawn
-// - current_piece_conflicts [determine game end]
-// - flip_right, flip_left, shift_right, shift_left, fall
-// - lock
-// - clear
-
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-struct SingleKey {
-    triggered: bool,
-    needs_service: bool,
-}
-
-impl SingleKey {
-    fn new() -> Self {
-        SingleKey {
-            triggered: false,
-            needs_service: false,
-        }
-    }
-    fn trigger(&mut self, press: bool) {
-        if press {
-            if !self.triggered {
-                self.triggered = true;
-                self.needs_service = true;
+fn render<R: Randomizer, D: DifficultyCurve>(
+    game: &Game<R, D>,
+    mut ctxt: graphics::context::Context,
+    gl: &mut opengl_graphics::GlGraphics,
+) {
+    use graphics::Transformed;
+    let dims = ctxt.get_view_size();
+    let scale = dims[0].min(dims[1]) / 290.0;
+    ctxt = ctxt
+        .scale(scale, scale)
+        .trans(20.0, 277.5)
+        .append_transform([[13.0, 5.0, 0.0], [0.0, -12.0, 0.0]]);
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const GRAY: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+
+    graphics::clear(BLACK, gl);
+
+    graphics::Rectangle::new(GRAY).draw(
+        [-0.5, -0.5, 11.0, 23.0],
+        &ctxt.draw_state,
+        ctxt.transform,
+        gl,
+    );
+    graphics::Rectangle::new(BLACK).draw(
+        [0.0, 0.0, 10.0, 22.0],
+        &ctxt.draw_state,
+        ctxt.transform,
+        gl,
+    );
+
+    let board = game.board();
+    // The whole stack is revealed once the game is over.
+    let reveal = game.state() == State::Loss || game.state() == State::Victory;
+    for x in 0..BOARD_WIDTH {
+        for y in 0..BOARD_HEIGHT {
+            if let Some(tet) = board.board()[x][y] {
+                let mut color = tetrhombino_color(tet);
+                color[3] = 0.5;
+                if let (Some(frames), false) = (board.visibility()[x][y], reveal) {
+                    // Vanishing blocks fade out over their last second.
+                    color[3] *= (frames as f32 / FRAMES_PER_SECOND as f32).min(1.0);
+                }
+                draw_rhomb((x as i8, y as i8), color, ctxt, gl);
             }
-        } else {
-            self.triggered = false;
         }
     }
-    fn service(&mut self) -> bool {
-        self.triggered && std::mem::replace(&mut self.needs_service, false)
-    }
-}
-
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-struct MultiKey {
-    state: SingleKey,
-    hold_frames: usize,
-}
 
-impl MultiKey {
-    fn new() -> Self {
-        MultiKey {
-            state: SingleKey::new(),
-            hold_frames: 0,
-        }
-    }
-    fn trigger(&mut self, press: bool) {
-        self.state.trigger(press);
-        if !press {
-            self.hold_frames = 0;
-        }
+    if game.state() == State::Falling || game.state() == State::Loss {
+        const SHADOW_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+        draw_tetrhombino(board.rotation(), board.get_shadow(), SHADOW_COLOR, ctxt, gl);
+        let color = tetrhombino_color(board.current().tetrhombino);
+        draw_tetrhombino(board.rotation(), board.current(), color, ctxt, gl);
     }
-    fn service(&mut self) -> bool {
-        if self.state.triggered {
-            self.hold_frames += 1;
-            self.state.service() || self.hold_frames >= 14
-        } else {
-            false
+
+    if game.state() != State::Loss {
+        // The first piece is shown full-size, and the rest smaller, in a
+        // column to the right of the board.
+        for (i, tet) in game.next().iter().enumerate() {
+            let (position, ctxt) = if i == 0 {
+                ((-4, 16), ctxt)
+            } else {
+                (
+                    (0, 0),
+                    ctxt.trans(12.0, 22.0 - 2.0 * i as f64).scale(0.5, 0.5),
+                )
+            };
+            draw_tetrhombino(
+                board.rotation(),
+                TetrhombinoState {
+                    tetrhombino: *tet,
+                    position,
+                    orientation: Orientation::Start,
+                },
+                tetrhombino_color(*tet),
+                ctxt,
+                gl,
+            );
         }
     }
-}
-
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-struct ContinuousKey {
-    pressed: bool,
-}
 
-impl ContinuousKey {
-    fn new() -> Self {
-        ContinuousKey { pressed: false }
-    }
-    fn trigger(&mut self, press: bool) {
-        self.pressed = press;
-    }
-    fn service(&mut self) -> bool {
-        self.pressed
+    if let Some(held) = game.held() {
+        let mut color = tetrhombino_color(held);
+        if !game.can_hold() {
+            color[3] = 0.5;
+        }
+        draw_tetrhombino(
+            board.rotation(),
+            TetrhombinoState {
+                tetrhombino: held,
+                position: (-4, 11),
+                orientation: Orientation::Start,
+            },
+            color,
+            ctxt,
+            gl,
+        );
     }
-}
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-struct KeyState {
-    left: MultiKey,
-    right: MultiKey,
-    sonic_drop: MultiKey,     // Drop all the way but do not lock
-    fast_drop: ContinuousKey, // Lock if fallen; otherwise drop a frame
-    r_left: SingleKey,
-    r_right: SingleKey,
-}
-
-impl KeyState {
-    fn new() -> KeyState {
-        KeyState {
-            left: MultiKey::new(),
-            right: MultiKey::new(),
-            fast_drop: ContinuousKey::new(),
-            sonic_drop: MultiKey::new(),
-            r_left: SingleKey::new(),
-            r_right: SingleKey::new(),
+    if let Some(grade) = game.grade() {
+        draw_grade(grade, ctxt.trans(-3.5, 8.0), gl);
+    }
+    draw_number(game.score(), ctxt.trans(15.0, 9.0).scale(0.5, 0.5), gl);
+    draw_number(game.level(), ctxt.trans(15.0, 6.0), gl);
+    draw_number(game.lines_cleared(), ctxt.trans(15.0, 3.0), gl);
+    // The clock counts down through the credit roll.
+    let time = match game.roll_frames_left() {
+        Some(frames) => frames as u64,
+        None => game.timer().frames(),
+    };
+    draw_text(
+        &format_time(time),
+        ctxt.trans(11.5, 0.5).scale(0.5, 0.5),
+        gl,
+    );
+
+    // Results go over the top of the board, leaving the stack visible: the
+    // time, then pieces per second, then keys per piece.
+    if let Some(result) = game.result() {
+        graphics::Rectangle::new(BLACK).draw(
+            [1.0, 13.0, 8.0, 8.0],
+            &ctxt.draw_state,
+            ctxt.transform,
+            gl,
+        );
+        let lines = [
+            format_time(result.time),
+            format!("{:.2}", result.pieces_per_second()),
+            format!("{:.2}", result.keys_per_piece()),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            let y = 19.0 - 2.5 * i as f64;
+            draw_text(line, ctxt.trans(2.0, y).scale(0.5, 0.5), gl);
         }
     }
 }
 
-trait DifficultyCurve {
-    fn get_gravity(&self) -> usize; // units are G/256
-    fn get_are_frames(&self) -> usize;
-    fn get_clear_frames(&self) -> usize;
-    fn get_lock_frames(&self) -> usize;
-    fn clear_lines(&mut self, lines: usize);
-    fn done(&self) -> bool;
+fn print_result(result: &GameResult) {
+    let outcome = if result.victory {
+        "cleared"
+    } else {
+        "game over"
+    };
+    let grade = match result.grade {
+        Some(grade) => format!(" grade {}", grade.name()),
+        None => String::new(),
+    };
+    println!(
+        "{}: score {}{} level {} lines {} time {}",
+        outcome,
+        result.score,
+        grade,
+        result.level,
+        result.lines,
+        format_time(result.time)
+    );
+    let splits: Vec<String> = result.splits.iter().map(|&s| format_time(s)).collect();
+    println!("splits: {}", splits.join(" "));
+    println!(
+        "pieces {} ({:.2}/s) keys {} ({:.2}/piece)",
+        result.pieces,
+        result.pieces_per_second(),
+        result.key_presses,
+        result.keys_per_piece()
+    );
 }
 
-#[derive(Debug, Copy, Clone)]
-struct NormalDifficulty {
-    lines_cleared: usize,
-}
-
-impl NormalDifficulty {
-    fn new() -> Self {
-        NormalDifficulty { lines_cleared: 0 }
-    }
-}
-
-impl DifficultyCurve for NormalDifficulty {
-    fn get_gravity(&self) -> usize {
-        const SPEED_TABLE: [usize; 15] =
-            [4, 8, 12, 16, 20, 24, 28, 32, 48, 64, 80, 96, 112, 128, 256];
-        SPEED_TABLE[self.lines_cleared / 10]
-    }
-    fn get_are_frames(&self) -> usize {
-        25
-    }
-    fn get_clear_frames(&self) -> usize {
-        40
-    }
-    fn get_lock_frames(&self) -> usize {
-        30
-    }
-    fn clear_lines(&mut self, lines: usize) {
-        self.lines_cleared += lines;
-    }
-    fn done(&self) -> bool {
-        self.lines_cleared >= 150
+fn tetrhombino_color(tet: Tetrhombino) -> [f32; 4] {
+    match tet {
+        Tetrhombino::O => [1.0, 1.0, 0.0, 1.0],
+        Tetrhombino::I => [0.0, 1.0, 1.0, 1.0],
+        Tetrhombino::S => [0.0, 1.0, 0.0, 1.0],
+        Tetrhombino::Z => [1.0, 0.0, 0.0, 1.0],
+        Tetrhombino::T => [1.0, 0.0, 1.0, 1.0],
+        Tetrhombino::L => [1.0, 0.5, 0.0, 1.0],
+        Tetrhombino::J => [0.0, 0.0, 1.0, 1.0],
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-enum State {
-    Start,
-    Falling,
-    Are(usize),   // frame count
-    Clear(usize), // frame count
-    Loss,
-    Victory,
+fn default_bindings() -> Bindings {
+    let mut bindings = Bindings::new();
+    let defaults = [
+        ("Left", Command::Play(Action::Left)),
+        ("Right", Command::Play(Action::Right)),
+        ("Up", Command::Play(Action::SonicDrop)),
+        ("Down", Command::Play(Action::FastDrop)),
+        ("Z", Command::Play(Action::RotateLeft)),
+        ("X", Command::Play(Action::RotateRight)),
+        ("A", Command::Play(Action::Rotate180)),
+        ("C", Command::Play(Action::Hold)),
+        ("P", Command::Pause),
+        ("R", Command::Restart),
+        ("Pad DPadLeft", Command::Play(Action::Left)),
+        ("Pad DPadRight", Command::Play(Action::Right)),
+        ("Pad DPadUp", Command::Play(Action::SonicDrop)),
+        ("Pad DPadDown", Command::Play(Action::FastDrop)),
+        ("Pad LeftStickX-", Command::Play(Action::Left)),
+        ("Pad LeftStickX+", Command::Play(Action::Right)),
+        ("Pad LeftStickY+", Command::Play(Action::SonicDrop)),
+        ("Pad LeftStickY-", Command::Play(Action::FastDrop)),
+        // Arcade layout: the bottom and left buttons rotate one way, the
+        // right one the other, and the top one holds.
+        ("Pad South", Command::Play(Action::RotateLeft)),
+        ("Pad East", Command::Play(Action::RotateRight)),
+        ("Pad West", Command::Play(Action::RotateLeft)),
+        ("Pad North", Command::Play(Action::Hold)),
+        ("Pad Start", Command::Pause),
+    ];
+    for &(key, command) in defaults.iter() {
+        bindings.bind(key, command);
+    }
+    bindings
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Game {
-    board: BoardState,
-    rand: TGMRandomizer, // todo parameterize.
-    keys: KeyState,
-    state: State,
-    stuck_frames: usize,
-    gravity_count: usize,
-    next: Tetrhombino,
-    lines_cleared: usize,
-    stage: NormalDifficulty, // todo parameterize.
+const DEFAULT_DEADZONE: f64 = 0.5;
+
+// Reads controllers through gilrs, since piston's glutin window doesn't report
+// them, and turns their buttons and sticks into presses and releases of named
+// inputs, like "Pad South" or "Pad LeftStickX-", so they can be bound just
+// like keys.  The names don't say which controller they came from: any pad
+// that gets plugged in works straight away, with the same bindings, and an
+// input stays held as long as any pad holds it.
+struct Controllers {
+    gilrs: Option<gilrs::Gilrs>,
+    deadzone: f32,
+    held: HashMap<gilrs::GamepadId, HashSet<String>>, // by each connected pad
 }
 
-impl Game {
-    fn new() -> Game {
-        Game {
-            board: BoardState::new(),
-            rand: TGMRandomizer::new(),
-            keys: KeyState::new(),
-            state: State::Start,
-            stuck_frames: 0,
-            gravity_count: 0,
-            next: Tetrhombino::I, // doesn't matter.
-            lines_cleared: 0,
-            stage: NormalDifficulty::new(),
-        }
-    }
-    fn spawn(&mut self) {
-        self.board.spawn(TetrhombinoState {
-            tetrhombino: self.next,
-            orientation: Orientation::Start,
-            position: START_POSITION,
-        });
-        if self.board.current_piece_conflicts() {
-            self.state = State::Loss;
-            return;
-        }
-        self.next = self.rand.get_piece();
-        self.state = State::Falling;
-        self.stuck_frames = 0;
-        self.gravity_count = 0;
-    }
-    fn lock(&mut self) {
-        self.board.lock();
-        let cleared = self.board.clear();
-        if cleared > 0 {
-            self.lines_cleared += cleared;
-            self.stage.clear_lines(cleared);
-            self.state = State::Clear(0);
-        } else {
-            self.state = State::Are(0);
-        }
-        if self.stage.done() {
-            self.state = State::Victory;
-        }
-    }
-    fn update(&mut self) {
-        if self.state == State::Loss || self.state == State::Victory {
-            return;
-        }
-
-        // Progress through inter-piece state machine; keep this in this order
-        // so that 0-frame Are and Clear phases work correctly.
-        if self.state == State::Start {
-            self.state = State::Are(0);
-            self.next = self.rand.get_piece();
-        }
-        if let State::Clear(n) = self.state {
-            if n >= self.stage.get_clear_frames() {
-                self.state = State::Are(0);
-            } else {
-                self.state = State::Clear(n + 1);
+impl Controllers {
+    fn new(deadzone: f64) -> Self {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    println!("controller found: {}", gamepad.name());
+                }
+                Some(gilrs)
             }
-        }
-        if let State::Are(n) = self.state {
-            if n >= self.stage.get_are_frames() {
-                self.spawn();
-            } else {
-                self.state = State::Are(n + 1);
-            }
-        }
-
-        // Handle DAS during ARE/Line-clear
-        if self.state != State::Falling {
-            self.keys.left.service();
-            self.keys.right.service();
-            return;
-        }
-
-        // Input
-        if self.keys.left.service() {
-            self.board.shift_left();
-        }
-        if self.keys.right.service() {
-            self.board.shift_right();
-        }
-        if self.keys.r_left.service() {
-            self.board.flip_left();
-        }
-        if self.keys.r_right.service() {
-            self.board.flip_right();
-        }
-        if self.keys.sonic_drop.service() {
-            while self.board.fall() {}
-        }
-        if self.keys.fast_drop.service() && !self.board.fall() {
-            self.lock();
-            return;
-        }
-
-        // Fall
-        if !self.board.stuck() {
-            self.stuck_frames = 0;
-            self.gravity_count += self.stage.get_gravity();
-            while self.gravity_count >= 256 {
-                self.board.fall();
-                self.gravity_count -= 256;
+            Err(e) => {
+                eprintln!("controllers unavailable: {}", e);
+                None
             }
+        };
+        Controllers {
+            gilrs,
+            deadzone: deadzone as f32,
+            held: HashMap::new(),
+        }
+    }
+    fn held_by_another(&self, id: gilrs::GamepadId, name: &str) -> bool {
+        self.held
+            .iter()
+            .any(|(&other, held)| other != id && held.contains(name))
+    }
+    fn set(&mut self, id: gilrs::GamepadId, name: String, press: bool) -> Option<(String, bool)> {
+        let elsewhere = self.held_by_another(id, &name);
+        let held = self.held.entry(id).or_default();
+        let changed = if press {
+            held.insert(name.clone())
         } else {
-            self.stuck_frames += 1;
-        }
-
-        // Lock
-        if self.board.stuck() {
-            self.gravity_count = 0;
-            if self.stuck_frames >= self.stage.get_lock_frames() {
-                self.lock();
-            }
-        }
-    }
-
-    fn input(&mut self, button: piston::input::keyboard::Key, press: bool) {
-        use piston::input::keyboard::Key;
-        match button {
-            Key::Left => self.keys.left.trigger(press),
-            Key::Right => self.keys.right.trigger(press),
-            Key::Up => self.keys.sonic_drop.trigger(press),
-            Key::Down => self.keys.fast_drop.trigger(press),
-            Key::Z => self.keys.r_left.trigger(press),
-            Key::X => self.keys.r_right.trigger(press),
-            _ => {} // TODO handle double-rotation in a consistent manner?
-        }
-    }
-
-    fn draw_rhomb(
-        &self,
-        (x, y): Position,
-        color: [f32; 4],
-        ctxt: graphics::context::Context,
-        gl: &mut opengl_graphics::GlGraphics,
-    ) {
-        graphics::Rectangle::new(color).draw(
-            [f64::from(x), f64::from(y), 1.0, 1.0],
-            &ctxt.draw_state,
-            ctxt.transform,
-            gl,
-        );
-    }
-
-    fn draw_tetrhombino(
-        &self,
-        state: TetrhombinoState,
-        color: [f32; 4],
-        ctxt: graphics::context::Context,
-        gl: &mut opengl_graphics::GlGraphics,
-    ) {
-        for pos in state.occupied_places().iter() {
-            self.draw_rhomb(*pos, color, ctxt, gl);
+            held.remove(&name)
+        };
+        if changed && !elsewhere {
+            Some((name, press))
+        } else {
+            None
         }
     }
-
-    fn draw_segment(
-        &self,
-        on: bool,
-        ctxt: graphics::context::Context,
-        gl: &mut opengl_graphics::GlGraphics,
-    ) {
-        const SEGMENT: [[f64; 2]; 6] = [
-            [0.05, 0.0],
-            [0.10, 0.05],
-            [0.90, 0.05],
-            [0.95, 0.0],
-            [0.90, -0.05],
-            [0.10, -0.05],
+    fn axis(&mut self, id: gilrs::GamepadId, axis: String, value: f32) -> Vec<(String, bool)> {
+        let directions = [
+            (format!("{}-", axis), value < -self.deadzone),
+            (format!("{}+", axis), value > self.deadzone),
         ];
-
-        const COLOR_ON: [f32; 4] = [0.0, 0.7, 1.0, 1.0];
-        if on {
-            graphics::polygon(COLOR_ON, &SEGMENT, ctxt.transform, gl);
+        // Releases first, so flicking straight across never holds both.
+        let mut inputs = Vec::new();
+        for &press in [false, true].iter() {
+            for (name, pushed) in directions.iter() {
+                if *pushed == press {
+                    inputs.extend(self.set(id, name.clone(), press));
+                }
+            }
         }
-    }
-
-    fn draw_digit(
-        &self,
-        digit: u8,
-        ctxt: graphics::context::Context,
-        gl: &mut opengl_graphics::GlGraphics,
-    ) {
-        //   4
-        // 0   2
-        //   5
-        // 1   3
-        //   6
-        const DIGITS: [[bool; 7]; 10] = [
-            [true, true, true, true, true, false, true],
-            [false, false, true, true, false, false, false],
-            [false, true, true, false, true, true, true],
-            [false, false, true, true, true, true, true],
-            [true, false, true, true, false, true, false],
-            [true, false, false, true, true, true, true],
-            [true, true, false, true, true, true, true],
-            [false, false, true, true, true, false, false],
-            [true, true, true, true, true, true, true],
-            [true, false, true, true, true, true, true],
-        ];
-
-        use graphics::Transformed;
-        let ctxts = [
-            ctxt.trans(0.0, 1.0).rot_deg(90.0),
-            ctxt.rot_deg(90.0),
-            ctxt.trans(1.0, 1.0).rot_deg(90.0),
-            ctxt.trans(1.0, 0.0).rot_deg(90.0),
-            ctxt.trans(0.0, 2.0),
-            ctxt.trans(0.0, 1.0),
-            ctxt,
-        ];
-
-        for (on, ctxt) in DIGITS[digit as usize].into_iter().zip(ctxts.into_iter()) {
-            self.draw_segment(*on, *ctxt, gl);
+        inputs
+    }
+    // Everything that happened since the last call.
+    fn poll(&mut self) -> Vec<(String, bool)> {
+        use gilrs::EventType;
+        let mut inputs = Vec::new();
+        while let Some(event) = self.gilrs.as_mut().and_then(|gilrs| gilrs.next_event()) {
+            let id = event.id;
+            match event.event {
+                EventType::ButtonPressed(button, code) => {
+                    inputs.extend(self.set(id, button_name(button, code), true))
+                }
+                EventType::ButtonReleased(button, code) => {
+                    inputs.extend(self.set(id, button_name(button, code), false))
+                }
+                EventType::AxisChanged(axis, value, code) => {
+                    let name = match axis {
+                        gilrs::Axis::Unknown => format!("Pad Code {}", code),
+                        axis => format!("Pad {:?}", axis),
+                    };
+                    inputs.extend(self.axis(id, name, value));
+                }
+                EventType::Connected => {
+                    if let Some(ref gilrs) = self.gilrs {
+                        println!("controller connected: {}", gilrs.gamepad(id).name());
+                    }
+                    self.held.entry(id).or_default();
+                }
+                // Let go of everything the pad was holding, or it would stay
+                // held for good.
+                EventType::Disconnected => {
+                    println!("controller disconnected");
+                    let held = self.held.remove(&id).unwrap_or_default();
+                    for name in held {
+                        if !self.held_by_another(id, &name) {
+                            inputs.push((name, false));
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
+        inputs
     }
+}
 
-    fn draw_number(
-        &self,
-        mut number: usize,
-        mut ctxt: graphics::context::Context,
-        gl: &mut opengl_graphics::GlGraphics,
-    ) {
-        if number == 0 {
-            self.draw_digit(0, ctxt, gl);
-            return;
-        }
-        while number > 0 {
-            self.draw_digit((number % 10) as u8, ctxt, gl);
-            number /= 10;
-            use graphics::Transformed;
-            ctxt = ctxt.trans(-1.5, 0.0);
-        }
+fn button_name(button: gilrs::Button, code: gilrs::ev::Code) -> String {
+    match button {
+        gilrs::Button::Unknown => format!("Pad Code {}", code),
+        button => format!("Pad {:?}", button),
     }
+}
 
-    fn render(&self, mut ctxt: graphics::context::Context, gl: &mut opengl_graphics::GlGraphics) {
-        use graphics::Transformed;
-        let dims = ctxt.get_view_size();
-        let scale = dims[0].min(dims[1]) / 290.0;
-        ctxt = ctxt
-            .scale(scale, scale)
-            .trans(20.0, 277.5)
-            .append_transform([[13.0, 5.0, 0.0], [0.0, -12.0, 0.0]]);
+// A broken bindings file shouldn't keep the game from starting.
+fn load_bindings_or_default(path: &std::path::Path) -> Bindings {
+    Bindings::load(path).unwrap_or_else(|e| {
+        eprintln!("could not load bindings from {}: {}", path.display(), e);
+        eprintln!("using the default bindings");
+        default_bindings()
+    })
+}
 
-        const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
-        const GRAY: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+// Uses the given file if there is one, and otherwise the user's saved
+// bindings, writing out the defaults the first time so there's something to
+// edit.
+fn load_bindings(path: Option<&String>) -> Bindings {
+    if let Some(path) = path {
+        return load_bindings_or_default(path.as_ref());
+    }
+    let path = match Bindings::config_path() {
+        Some(path) => path,
+        None => return default_bindings(),
+    };
+    if path.exists() {
+        return load_bindings_or_default(&path);
+    }
+    let bindings = default_bindings();
+    match bindings.save(&path) {
+        Ok(()) => println!("wrote default bindings to {}", path.display()),
+        Err(e) => eprintln!("could not save bindings to {}: {}", path.display(), e),
+    }
+    bindings
+}
 
-        graphics::clear(BLACK, gl);
+struct Options {
+    mode: Mode,
+    seed: Option<u64>,
+    record: Option<String>,
+    replay: Option<String>,
+    rules: Option<Rules>,
+    rotation: Option<String>,
+    lock_reset: Option<LockReset>,
+    grading: Option<Grading>,
+    credit_roll: Option<CreditRoll>,
+    handling: Handling,
+    bindings: Option<String>,
+    deadzone: f64,
+}
 
-        graphics::Rectangle::new(GRAY).draw(
-            [-0.5, -0.5, 11.0, 23.0],
-            &ctxt.draw_state,
-            ctxt.transform,
-            gl,
-        );
-        graphics::Rectangle::new(BLACK).draw(
-            [0.0, 0.0, 10.0, 22.0],
-            &ctxt.draw_state,
-            ctxt.transform,
-            gl,
-        );
+fn usage() -> ! {
+    eprintln!("usage: rhombus-instinct [--mode MODE] [--seed N] [--record FILE] [--replay FILE]");
+    eprintln!("                        [--rules classic|modern|sprint]");
+    eprintln!("                        [--rotation classic|ti|srs|FILE]");
+    eprintln!("                        [--lock-reset step|move|infinite]");
+    eprintln!("                        [--grading off|score|points]");
+    eprintln!("                        [--roll off|normal|fading|invisible]");
+    eprintln!("                        [--das FRAMES] [--arr FRAMES] [--das-cut FRAMES]");
+    eprintln!("                        [--bindings FILE] [--deadzone 0.0-1.0]");
+    let modes: Vec<&str> = Mode::ALL.iter().map(|m| m.name()).collect();
+    eprintln!("modes: {}", modes.join(", "));
+    std::process::exit(2);
+}
 
-        for x in 0..10 {
-            for y in 0..22 {
-                if let Some(tet) = self.board.board[x][y] {
-                    let mut color = tetrhombino_color(tet);
-                    color[3] = 0.5;
-                    self.draw_rhomb((x as i8, y as i8), color, ctxt, gl);
-                }
+fn parse_options() -> Options {
+    let mut options = Options {
+        mode: Mode::Normal,
+        seed: None,
+        record: None,
+        replay: None,
+        rules: None,
+        rotation: None,
+        lock_reset: None,
+        grading: None,
+        credit_roll: None,
+        handling: Handling::new(),
+        bindings: None,
+        deadzone: DEFAULT_DEADZONE,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--mode" => options.mode = Mode::from_name(&value).unwrap_or_else(|| usage()),
+            "--seed" => options.seed = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--record" => options.record = Some(value),
+            "--replay" => options.replay = Some(value),
+            "--rules" => options.rules = Some(rules_from_name(&value).unwrap_or_else(|| usage())),
+            "--rotation" => options.rotation = Some(value),
+            "--lock-reset" => {
+                options.lock_reset = Some(LockReset::from_name(&value).unwrap_or_else(|| usage()))
             }
+            "--grading" => {
+                options.grading = Some(Grading::from_name(&value).unwrap_or_else(|| usage()))
+            }
+            "--roll" => {
+                options.credit_roll = Some(CreditRoll::from_name(&value).unwrap_or_else(|| usage()))
+            }
+            "--das" => options.handling.das = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--arr" => options.handling.arr = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--das-cut" => options.handling.das_cut = value.parse().unwrap_or_else(|_| usage()),
+            "--bindings" => options.bindings = Some(value),
+            "--deadzone" => options.deadzone = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
         }
-
-        if self.state == State::Falling || self.state == State::Loss {
-            const SHADOW_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
-            self.draw_tetrhombino(self.board.get_shadow(), SHADOW_COLOR, ctxt, gl);
-            let color = tetrhombino_color(self.board.current.tetrhombino);
-            self.draw_tetrhombino(self.board.current, color, ctxt, gl);
-        }
-
-        if self.state != State::Loss {
-            let color = tetrhombino_color(self.next);
-            self.draw_tetrhombino(
-                TetrhombinoState {
-                    tetrhombino: self.next,
-                    position: (-4, 16),
-                    orientation: Orientation::Start,
-                },
-                color,
-                ctxt,
-                gl,
-            );
-        }
-
-        self.draw_number(self.lines_cleared, ctxt.trans(15.0, 3.0), gl);
     }
+    options
 }
 
-fn tetrhombino_color(tet: Tetrhombino) -> [f32; 4] {
-    match tet {
-        Tetrhombino::O => [1.0, 1.0, 0.0, 1.0],
-        Tetrhombino::I => [0.0, 1.0, 1.0, 1.0],
-        Tetrhombino::S => [0.0, 1.0, 0.0, 1.0],
-        Tetrhombino::Z => [1.0, 0.0, 0.0, 1.0],
-        Tetrhombino::T => [1.0, 0.0, 1.0, 1.0],
-        Tetrhombino::L => [1.0, 0.5, 0.0, 1.0],
-        Tetrhombino::J => [0.0, 0.0, 1.0, 1.0],
+// The base rulesets a player can start from instead of the mode's own.
+fn rules_from_name(name: &str) -> Option<Rules> {
+    match name {
+        "classic" => Some(Rules::classic()),
+        "modern" => Some(Rules::modern()),
+        "sprint" => Some(Rules::sprint()),
+        _ => None,
     }
 }
 
+fn rules_from_options(options: &Options) -> Rules {
+    let mut rules = options
+        .rules
+        .clone()
+        .unwrap_or_else(|| options.mode.rules());
+    if let Some(ref path) = options.rotation {
+        let rotation = RotationSystem::builtin(path)
+            .unwrap_or_else(|| RotationSystem::load(path).expect("could not load rotation system"));
+        println!("rotation system: {}", rotation.name());
+        rules.rotation = std::sync::Arc::new(rotation);
+    }
+    if let Some(lock_reset) = options.lock_reset {
+        println!("lock reset: {}", lock_reset.name());
+        rules.lock_reset = lock_reset;
+    }
+    if let Some(grading) = options.grading {
+        println!("grading: {}", grading.name());
+        rules.grading = grading;
+    }
+    if let Some(credit_roll) = options.credit_roll {
+        println!("credit roll: {}", credit_roll.name());
+        rules.credit_roll = credit_roll;
+    }
+    rules
+}
+
 fn main() {
+    let options = parse_options();
+    let replay = options.replay.as_ref().map(|path| {
+        let file = std::fs::File::open(path).expect("could not open replay");
+        Replay::read(std::io::BufReader::new(file)).expect("could not read replay")
+    });
+    let bindings = load_bindings(options.bindings.as_ref());
+
     // Change this to OpenGL::V2_1 if not working.
     let opengl = opengl_graphics::OpenGL::V3_2;
 
@@ -877,27 +655,108 @@ fn main() {
 
     // Create a new game and run it.
     let mut gl = opengl_graphics::GlGraphics::new(opengl);
-    let mut game = Game::new();
+    // A replay brings its own rules and handling, whatever the options say.
+    let (mode, rules, seed, handling) = match replay {
+        Some(ref replay) => (
+            replay.mode,
+            replay.rules.clone(),
+            replay.seed,
+            replay.handling,
+        ),
+        None => (
+            options.mode,
+            rules_from_options(&options),
+            options.seed.unwrap_or_else(rand::random),
+            options.handling,
+        ),
+    };
+    println!("seed: {}", seed);
+    let new_game = |seed| {
+        let mut game = mode.new_game_with_rules(seed, rules.clone());
+        game.set_handling(handling);
+        game
+    };
+    let mut game = new_game(seed);
+    let mut recording = Replay::new(mode, rules.clone(), seed, handling);
+    let mut playback = replay.as_ref().map(Playback::new);
+    let mut paused = false;
+    let mut reported = false; // whether we've printed the result yet
+    let mut controllers = Controllers::new(options.deadzone);
     use piston::event_loop::EventLoop;
+    use piston::input::ButtonEvent;
 
     let mut settings = piston::event_loop::EventSettings::new();
-    settings.set_max_fps(60);
-    settings.set_ups(0);
+    settings.set_max_fps(FRAMES_PER_SECOND);
+    // Run the game logic on piston's fixed-rate update events, independent of
+    // how often we get to render.  If we fall behind, catch up on up to half a
+    // second of missed frames before giving up and skipping ahead.
+    settings.set_ups(FRAMES_PER_SECOND);
+    settings.set_ups_reset(FRAMES_PER_SECOND / 2);
 
     let mut events = piston::event_loop::Events::new(settings);
     while let Some(e) = events.next(&mut window) {
-        match e {
-            piston::input::Event::Loop(piston::input::Loop::Render(r)) => {
-                game.update();
-                gl.draw(r.viewport(), |c, gl| game.render(c, gl));
+        let mut inputs = controllers.poll();
+        if let Some(args) = e.button_args() {
+            if let piston::input::Button::Keyboard(key) = args.button {
+                let press = args.state == piston::input::ButtonState::Press;
+                inputs.push((format!("{:?}", key), press));
+            }
+        }
+        for (name, press) in inputs {
+            let command = bindings.command(&name);
+            match command {
+                Some(Command::Play(action)) if playback.is_none() => {
+                    // Let go of keys released while paused, but don't
+                    // take new presses until the game resumes.
+                    if press && paused {
+                        continue;
+                    }
+                    recording.input(&mut game, action, press);
+                }
+                Some(Command::Pause) if press => paused = !paused,
+                Some(Command::Restart) if press => {
+                    // Replays start over; live games get a new seed
+                    // unless one was given on the command line.
+                    let seed = match replay {
+                        Some(ref replay) => replay.seed,
+                        None => options.seed.unwrap_or_else(rand::random),
+                    };
+                    println!("seed: {}", seed);
+                    game = new_game(seed);
+                    recording = Replay::new(mode, rules.clone(), seed, handling);
+                    playback = replay.as_ref().map(Playback::new);
+                    paused = false;
+                    reported = false;
+                }
+                _ => {}
             }
-            piston::input::Event::Input(piston::input::Input::Button(args)) => {
-                // println!("{:?}", args);
-                if let piston::input::Button::Keyboard(key) = args.button {
-                    game.input(key, args.state == piston::input::ButtonState::Press);
+        }
+        match e {
+            piston::input::Event::Loop(piston::input::Loop::Update(_)) => {
+                match playback {
+                    _ if paused => {}
+                    Some(ref mut playback) => playback.update(&mut game),
+                    None => recording.update(&mut game),
+                }
+                match game.result() {
+                    Some(ref result) if !reported => {
+                        print_result(result);
+                        reported = true;
+                    }
+                    _ => {}
                 }
             }
+            piston::input::Event::Loop(piston::input::Loop::Render(r)) => {
+                gl.draw(r.viewport(), |c, gl| render(&game, c, gl));
+            }
             _ => {}
         };
     }
+
+    if let Some(path) = options.record {
+        let file = std::fs::File::create(path).expect("could not create replay");
+        recording
+            .write(std::io::BufWriter::new(file))
+            .expect("could not write replay");
+    }
 }
@@ -0,0 +1,74 @@
+use crate::difficulty::{
+    DeathDifficulty, DifficultyCurve, MasterDifficulty, NormalDifficulty, ShiraseDifficulty,
+    SprintDifficulty,
+};
+use crate::game::Game;
+use crate::randomizer::{BagRandomizer, Randomizer, TGMRandomizer};
+use crate::rules::Rules;
+
+pub type DynGame = Game<Box<dyn Randomizer>, Box<dyn DifficultyCurve>>;
+
+// The modes a player can pick from.  Each one is a recipe for building a
+// `Game`, and has a stable name so it can be recorded in replays.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Mode {
+    Normal,
+    Master,
+    Death,
+    Shirase,
+    Sprint,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Normal,
+        Mode::Master,
+        Mode::Death,
+        Mode::Shirase,
+        Mode::Sprint,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Master => "master",
+            Mode::Death => "death",
+            Mode::Shirase => "shirase",
+            Mode::Sprint => "sprint",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.iter().cloned().find(|m| m.name() == name)
+    }
+    pub fn rules(self) -> Rules {
+        match self {
+            Mode::Normal => Rules::classic(),
+            Mode::Master => Rules::classic(),
+            Mode::Death => Rules::classic(),
+            Mode::Shirase => Rules::modern(),
+            Mode::Sprint => Rules::sprint(),
+        }
+    }
+    fn randomizer(self, seed: u64) -> Box<dyn Randomizer> {
+        match self {
+            Mode::Sprint => Box::new(BagRandomizer::new(seed)),
+            _ => Box::new(TGMRandomizer::new(seed)),
+        }
+    }
+    fn difficulty(self) -> Box<dyn DifficultyCurve> {
+        match self {
+            Mode::Normal => Box::new(NormalDifficulty::new()),
+            Mode::Master => Box::new(MasterDifficulty::new()),
+            Mode::Death => Box::new(DeathDifficulty::new()),
+            Mode::Shirase => Box::new(ShiraseDifficulty::new()),
+            Mode::Sprint => Box::new(SprintDifficulty::new()),
+        }
+    }
+    pub fn new_game(self, seed: u64) -> DynGame {
+        self.new_game_with_rules(seed, self.rules())
+    }
+    // For trying out variations on a mode's rules.
+    pub fn new_game_with_rules(self, seed: u64, rules: Rules) -> DynGame {
+        Game::with_mode(self.randomizer(seed), self.difficulty(), rules)
+    }
+}
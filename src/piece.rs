@@ -0,0 +1,111 @@
+// The shapes of the pieces, and how they kick, live in the rotation system;
+// see `rotation/classic.txt` for pictures.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tetrhombino {
+    I,
+    O,
+    S,
+    Z,
+    L,
+    J,
+    T,
+}
+
+impl Tetrhombino {
+    pub const ALL: [Tetrhombino; 7] = [
+        Tetrhombino::I,
+        Tetrhombino::O,
+        Tetrhombino::S,
+        Tetrhombino::Z,
+        Tetrhombino::L,
+        Tetrhombino::J,
+        Tetrhombino::T,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Tetrhombino::I => "I",
+            Tetrhombino::O => "O",
+            Tetrhombino::S => "S",
+            Tetrhombino::Z => "Z",
+            Tetrhombino::L => "L",
+            Tetrhombino::J => "J",
+            Tetrhombino::T => "T",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Tetrhombino> {
+        Tetrhombino::ALL.iter().cloned().find(|t| t.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Start,
+    Right,
+    Both,
+    Left,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 4] = [
+        Orientation::Start,
+        Orientation::Right,
+        Orientation::Both,
+        Orientation::Left,
+    ];
+
+    pub fn flip_right(&mut self) {
+        *self = match *self {
+            Orientation::Start => Orientation::Right,
+            Orientation::Right => Orientation::Start,
+            Orientation::Both => Orientation::Left,
+            Orientation::Left => Orientation::Both,
+        }
+    }
+    pub fn flip_left(&mut self) {
+        *self = match *self {
+            Orientation::Start => Orientation::Left,
+            Orientation::Right => Orientation::Both,
+            Orientation::Both => Orientation::Right,
+            Orientation::Left => Orientation::Start,
+        }
+    }
+    // The half turn: the same as flipping both ways.
+    pub fn flip_both(&mut self) {
+        *self = match *self {
+            Orientation::Start => Orientation::Both,
+            Orientation::Right => Orientation::Left,
+            Orientation::Both => Orientation::Start,
+            Orientation::Left => Orientation::Right,
+        }
+    }
+    pub fn index(self) -> usize {
+        self as usize
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::Start => "start",
+            Orientation::Right => "right",
+            Orientation::Both => "both",
+            Orientation::Left => "left",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Orientation> {
+        Orientation::ALL.iter().cloned().find(|o| o.name() == name)
+    }
+}
+
+pub type Position = (i8, i8);
+
+pub const START_POSITION: Position = (4, 20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TetrhombinoState {
+    pub tetrhombino: Tetrhombino,
+    pub orientation: Orientation,
+    pub position: Position,
+}
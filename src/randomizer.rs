@@ -0,0 +1,176 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::piece::Tetrhombino;
+
+// Every randomizer is built from an explicit seed, and must produce the same
+// sequence of pieces every time it is built from that seed.  That rules out
+// `StdRng`, whose algorithm may change between rand releases; use a named
+// one instead.
+pub trait Randomizer {
+    fn get_piece(&mut self) -> Tetrhombino;
+    fn seed(&self) -> u64;
+}
+
+impl<R: Randomizer + ?Sized> Randomizer for Box<R> {
+    fn get_piece(&mut self) -> Tetrhombino {
+        (**self).get_piece()
+    }
+    fn seed(&self) -> u64 {
+        (**self).seed()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TGMRandomizer {
+    history: [Tetrhombino; 4],
+    pieces_given: usize,
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl TGMRandomizer {
+    pub fn new(seed: u64) -> Self {
+        TGMRandomizer {
+            history: [
+                Tetrhombino::Z,
+                Tetrhombino::Z,
+                Tetrhombino::S,
+                Tetrhombino::S,
+            ],
+            pieces_given: 0,
+            seed,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+    fn helper(&mut self) -> Tetrhombino {
+        if self.pieces_given == 0 {
+            [
+                Tetrhombino::I,
+                Tetrhombino::T,
+                Tetrhombino::L,
+                Tetrhombino::J,
+            ][self.rng.gen_range(0, 4)]
+        } else {
+            [
+                Tetrhombino::O,
+                Tetrhombino::I,
+                Tetrhombino::S,
+                Tetrhombino::Z,
+                Tetrhombino::T,
+                Tetrhombino::L,
+                Tetrhombino::J,
+            ][self.rng.gen_range(0, 7)]
+        }
+    }
+}
+
+impl Randomizer for TGMRandomizer {
+    fn get_piece(&mut self) -> Tetrhombino {
+        let mut res = self.helper();
+        for _ in 0..6 {
+            if !self.history.contains(&res) {
+                break;
+            }
+            res = self.helper();
+        }
+        self.history[self.pieces_given % 4] = res;
+        self.pieces_given += 1;
+        res
+    }
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+// Deals out all seven pieces in a random order, then shuffles them again.
+#[derive(Debug, Clone)]
+pub struct BagRandomizer {
+    bag: Vec<Tetrhombino>, // what's left of the current bag
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl BagRandomizer {
+    pub fn new(seed: u64) -> Self {
+        BagRandomizer {
+            bag: Vec::new(),
+            seed,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn get_piece(&mut self) -> Tetrhombino {
+        if self.bag.is_empty() {
+            self.bag = vec![
+                Tetrhombino::O,
+                Tetrhombino::I,
+                Tetrhombino::S,
+                Tetrhombino::Z,
+                Tetrhombino::T,
+                Tetrhombino::L,
+                Tetrhombino::J,
+            ];
+            self.bag.shuffle(&mut self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_pieces<R: Randomizer>(mut rand: R, n: usize) -> String {
+        (0..n).map(|_| format!("{:?}", rand.get_piece())).collect()
+    }
+
+    // If these change, every recorded replay plays out differently.
+    #[test]
+    fn tgm_sequence_is_pinned() {
+        assert_eq!(
+            first_pieces(TGMRandomizer::new(0), 20),
+            "LOZIJTSOIZJSTLIJOTLS"
+        );
+        assert_eq!(
+            first_pieces(TGMRandomizer::new(1234), 20),
+            "LOIZJTLOIJSZTLLOSJZL"
+        );
+    }
+
+    #[test]
+    fn bag_sequence_is_pinned() {
+        assert_eq!(
+            first_pieces(BagRandomizer::new(0), 21),
+            "ISZLOTJOTSZILJJOZITLS"
+        );
+        assert_eq!(
+            first_pieces(BagRandomizer::new(1234), 21),
+            "OISZJLTTJILZOSISZLJTO"
+        );
+    }
+
+    #[test]
+    fn bags_hold_every_piece() {
+        let pieces = first_pieces(BagRandomizer::new(99), 70);
+        for bag in pieces.as_bytes().chunks(7) {
+            let mut bag = bag.to_vec();
+            bag.sort();
+            assert_eq!(bag, b"IJLOSTZ");
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in 0..100 {
+            let first = TGMRandomizer::new(seed).get_piece();
+            assert!(![Tetrhombino::S, Tetrhombino::Z, Tetrhombino::O].contains(&first));
+        }
+    }
+}
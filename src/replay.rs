@@ -0,0 +1,417 @@
+// A replay is the mode, rules and seed a game was started with and the
+// player's handling settings, plus every input it received and the frame it
+// arrived on.  Since the engine is deterministic, that is enough to reproduce
+// the whole game.
+//
+// The file format is line-oriented text:
+//
+//   rhombus-instinct replay 3
+//   mode normal
+//   seed 1234
+//   rotation ti
+//   lock-reset move 15
+//   grading points
+//   roll fading
+//   preview 3
+//   hold on
+//   initial-hold on
+//   das curve
+//   arr 0
+//   das-cut 0
+//   frames 5678
+//   input 30 left press
+//   input 34 left release
+//   ...
+//
+// An input recorded on frame N is applied after N calls to `Game::update`.
+// The handling lines are optional, defaulting to `Handling::new()`; `curve`
+// means the value comes from the mode's difficulty curve.
+//
+// Rules left out of the file are the mode's own.  A built-in rotation system
+// is recorded by name; anything else is recorded in full, as a `rotation-def`
+// line for each line of its file.
+
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+use crate::difficulty::DifficultyCurve;
+use crate::game::Game;
+use crate::grade::Grading;
+use crate::keys::{Action, Handling};
+use crate::lock::LockReset;
+use crate::mode::{DynGame, Mode};
+use crate::randomizer::Randomizer;
+use crate::roll::CreditRoll;
+use crate::rotation::RotationSystem;
+use crate::rules::Rules;
+
+pub const REPLAY_VERSION: u32 = 3;
+const MAGIC: &str = "rhombus-instinct replay";
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ReplayInput {
+    pub frame: u64,
+    pub action: Action,
+    pub press: bool,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Replay {
+    pub mode: Mode,
+    pub rules: Rules,
+    pub seed: u64,
+    pub handling: Handling,
+    pub frames: u64, // total length of the recording
+    pub inputs: Vec<ReplayInput>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Handling frame counts that can defer to the difficulty curve.
+fn frames_name(frames: Option<usize>) -> String {
+    match frames {
+        Some(n) => n.to_string(),
+        None => "curve".to_string(),
+    }
+}
+
+fn frames_from_name(name: &str) -> Option<Option<usize>> {
+    match name {
+        "curve" => Some(None),
+        n => n.parse().ok().map(Some),
+    }
+}
+
+fn switch_name(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn switch_from_name(name: &str) -> Option<bool> {
+    match name {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+impl Replay {
+    pub fn new(mode: Mode, rules: Rules, seed: u64, handling: Handling) -> Self {
+        Replay {
+            mode,
+            rules,
+            seed,
+            handling,
+            frames: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    // Call this instead of `Game::input` to record the input as well.
+    pub fn input<R: Randomizer, D: DifficultyCurve>(
+        &mut self,
+        game: &mut Game<R, D>,
+        action: Action,
+        press: bool,
+    ) {
+        self.inputs.push(ReplayInput {
+            frame: game.frame(),
+            action,
+            press,
+        });
+        game.input(action, press);
+    }
+
+    // Call this instead of `Game::update` to record the frame as well.
+    pub fn update<R: Randomizer, D: DifficultyCurve>(&mut self, game: &mut Game<R, D>) {
+        game.update();
+        self.frames = game.frame();
+    }
+
+    // Plays the whole replay through a fresh game, returning the game as it
+    // stood at the end of the recording.
+    pub fn play(&self) -> DynGame {
+        let mut game = self.mode.new_game_with_rules(self.seed, self.rules.clone());
+        game.set_handling(self.handling);
+        let mut playback = Playback::new(self);
+        while !playback.finished(&game) {
+            playback.update(&mut game);
+        }
+        game
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{} {}", MAGIC, REPLAY_VERSION)?;
+        writeln!(out, "mode {}", self.mode.name())?;
+        writeln!(out, "seed {}", self.seed)?;
+        let rotation = &*self.rules.rotation;
+        if RotationSystem::builtin(rotation.name()).as_ref() == Some(rotation) {
+            writeln!(out, "rotation {}", rotation.name())?;
+        } else {
+            let mut definition = Vec::new();
+            rotation.write(&mut definition)?;
+            for line in String::from_utf8_lossy(&definition).lines() {
+                writeln!(out, "rotation-def {}", line)?;
+            }
+        }
+        match self.rules.lock_reset {
+            LockReset::Move(resets) => writeln!(out, "lock-reset move {}", resets)?,
+            reset => writeln!(out, "lock-reset {}", reset.name())?,
+        }
+        writeln!(out, "grading {}", self.rules.grading.name())?;
+        writeln!(out, "roll {}", self.rules.credit_roll.name())?;
+        writeln!(out, "preview {}", self.rules.preview)?;
+        writeln!(out, "hold {}", switch_name(self.rules.hold))?;
+        writeln!(out, "initial-hold {}", switch_name(self.rules.initial_hold))?;
+        writeln!(out, "das {}", frames_name(self.handling.das))?;
+        writeln!(out, "arr {}", frames_name(self.handling.arr))?;
+        writeln!(out, "das-cut {}", self.handling.das_cut)?;
+        writeln!(out, "frames {}", self.frames)?;
+        for input in self.inputs.iter() {
+            let press = if input.press { "press" } else { "release" };
+            writeln!(
+                out,
+                "input {} {} {}",
+                input.frame,
+                input.action.name(),
+                press
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(input: R) -> io::Result<Self> {
+        let mut lines = input.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| invalid("empty replay".to_string()))??;
+        let version = header
+            .strip_prefix(MAGIC)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| invalid(format!("not a replay: {:?}", header)))?;
+        if version != REPLAY_VERSION {
+            return Err(invalid(format!("unsupported replay version {}", version)));
+        }
+
+        let mut mode = None;
+        let mut seed = None;
+        let mut rotation = None;
+        let mut rotation_def = String::new();
+        let mut lock_reset = None;
+        let mut grading = None;
+        let mut credit_roll = None;
+        let mut preview = None;
+        let mut hold = None;
+        let mut initial_hold = None;
+        let mut handling = Handling::new();
+        let mut frames = None;
+        let mut inputs = Vec::new();
+        for line in lines {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let bad_line = || invalid(format!("bad replay line: {:?}", line));
+            match words.as_slice() {
+                [] => {}
+                ["mode", name] => mode = Some(Mode::from_name(name).ok_or_else(bad_line)?),
+                ["seed", n] => seed = Some(n.parse().map_err(|_| bad_line())?),
+                ["rotation", name] => {
+                    rotation = Some(RotationSystem::builtin(name).ok_or_else(bad_line)?)
+                }
+                ["rotation-def", rest @ ..] => {
+                    rotation_def.push_str(&rest.join(" "));
+                    rotation_def.push('\n');
+                }
+                ["lock-reset", "move", n] => {
+                    lock_reset = Some(LockReset::Move(n.parse().map_err(|_| bad_line())?))
+                }
+                ["lock-reset", name] => {
+                    lock_reset = Some(LockReset::from_name(name).ok_or_else(bad_line)?)
+                }
+                ["grading", name] => grading = Some(Grading::from_name(name).ok_or_else(bad_line)?),
+                ["roll", name] => {
+                    credit_roll = Some(CreditRoll::from_name(name).ok_or_else(bad_line)?)
+                }
+                ["preview", n] => preview = Some(n.parse().map_err(|_| bad_line())?),
+                ["hold", on] => hold = Some(switch_from_name(on).ok_or_else(bad_line)?),
+                ["initial-hold", on] => {
+                    initial_hold = Some(switch_from_name(on).ok_or_else(bad_line)?)
+                }
+                ["das", n] => handling.das = frames_from_name(n).ok_or_else(bad_line)?,
+                ["arr", n] => handling.arr = frames_from_name(n).ok_or_else(bad_line)?,
+                ["das-cut", n] => handling.das_cut = n.parse().map_err(|_| bad_line())?,
+                ["frames", n] => frames = Some(n.parse().map_err(|_| bad_line())?),
+                ["input", frame, action, press] => inputs.push(ReplayInput {
+                    frame: frame.parse().map_err(|_| bad_line())?,
+                    action: Action::from_name(action).ok_or_else(bad_line)?,
+                    press: match *press {
+                        "press" => true,
+                        "release" => false,
+                        _ => return Err(bad_line()),
+                    },
+                }),
+                _ => return Err(bad_line()),
+            }
+        }
+
+        let missing = |field| invalid(format!("replay has no {}", field));
+        let mode: Mode = mode.ok_or_else(|| missing("mode"))?;
+        if !rotation_def.is_empty() {
+            rotation = Some(RotationSystem::read(rotation_def.as_bytes())?);
+        }
+        let mut rules = mode.rules();
+        if let Some(rotation) = rotation {
+            rules.rotation = Arc::new(rotation);
+        }
+        rules.lock_reset = lock_reset.unwrap_or(rules.lock_reset);
+        rules.grading = grading.unwrap_or(rules.grading);
+        rules.credit_roll = credit_roll.unwrap_or(rules.credit_roll);
+        rules.preview = preview.unwrap_or(rules.preview);
+        rules.hold = hold.unwrap_or(rules.hold);
+        rules.initial_hold = initial_hold.unwrap_or(rules.initial_hold);
+        Ok(Replay {
+            mode,
+            rules,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            handling,
+            frames: frames.ok_or_else(|| missing("frames"))?,
+            inputs,
+        })
+    }
+}
+
+// Feeds a replay's inputs into a game one frame at a time, so that it can be
+// watched rather than just run to completion.
+#[derive(Debug, Clone)]
+pub struct Playback<'a> {
+    replay: &'a Replay,
+    next_input: usize,
+}
+
+impl<'a> Playback<'a> {
+    pub fn new(replay: &'a Replay) -> Self {
+        Playback {
+            replay,
+            next_input: 0,
+        }
+    }
+    pub fn finished<R: Randomizer, D: DifficultyCurve>(&self, game: &Game<R, D>) -> bool {
+        game.frame() >= self.replay.frames
+    }
+    // Call this instead of `Game::update`.
+    pub fn update<R: Randomizer, D: DifficultyCurve>(&mut self, game: &mut Game<R, D>) {
+        if self.finished(game) {
+            return;
+        }
+        while let Some(input) = self.replay.inputs.get(self.next_input) {
+            if input.frame > game.frame() {
+                break;
+            }
+            game.input(input.action, input.press);
+            self.next_input += 1;
+        }
+        game.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records a game played with a fixed, busy pattern of inputs.
+    fn record(mode: Mode, seed: u64, frames: u64) -> (Replay, DynGame) {
+        record_with_rules(mode, mode.rules(), seed, frames)
+    }
+
+    fn record_with_rules(mode: Mode, rules: Rules, seed: u64, frames: u64) -> (Replay, DynGame) {
+        let mut handling = Handling::new();
+        handling.arr = Some(0);
+        handling.das_cut = 2;
+        let mut game = mode.new_game_with_rules(seed, rules.clone());
+        game.set_handling(handling);
+        let mut replay = Replay::new(mode, rules, seed, handling);
+        for frame in 0..frames {
+            let action = Action::ALL[(frame / 7) as usize % Action::ALL.len()];
+            match frame % 7 {
+                0 => replay.input(&mut game, action, true),
+                3 => replay.input(&mut game, action, false),
+                _ => {}
+            }
+            replay.update(&mut game);
+        }
+        (replay, game)
+    }
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let (replay, _) = record(Mode::Normal, 42, 600);
+        let mut file = Vec::new();
+        replay.write(&mut file).unwrap();
+        assert_eq!(Replay::read(&file[..]).unwrap(), replay);
+    }
+
+    #[test]
+    fn playing_back_reproduces_the_game() {
+        for &mode in Mode::ALL.iter() {
+            let (replay, original) = record(mode, 7, 3000);
+            assert!(original.pieces() > 10);
+            let played = replay.play();
+            assert_eq!(played.frame(), original.frame());
+            assert_eq!(played.state(), original.state());
+            assert_eq!(played.board().board(), original.board().board());
+            assert_eq!(played.score(), original.score());
+            assert_eq!(played.lines_cleared(), original.lines_cleared());
+            assert_eq!(played.result(), original.result());
+        }
+    }
+
+    #[test]
+    fn rotation_systems_are_recorded() {
+        let srs = RotationSystem::srs();
+        let mut custom = Vec::new();
+        srs.write(&mut custom).unwrap();
+        let custom = String::from_utf8(custom)
+            .unwrap()
+            .replace("name srs", "name mine");
+        let custom = RotationSystem::read(custom.as_bytes()).unwrap();
+        for rotation in [srs, custom].iter() {
+            let mut rules = Mode::Normal.rules();
+            rules.rotation = Arc::new(rotation.clone());
+            let (replay, original) = record_with_rules(Mode::Normal, rules, 5, 600);
+            let mut file = Vec::new();
+            replay.write(&mut file).unwrap();
+            let read = Replay::read(&file[..]).unwrap();
+            assert_eq!(*read.rules.rotation, *rotation);
+            assert_eq!(read.play().board().board(), original.board().board());
+        }
+    }
+
+    #[test]
+    fn rules_are_recorded() {
+        let mut rules = Mode::Master.rules();
+        rules.lock_reset = LockReset::Move(10);
+        rules.grading = Grading::Off;
+        rules.credit_roll = CreditRoll::Invisible;
+        rules.preview = 4;
+        rules.hold = true;
+        rules.initial_hold = false;
+        let (replay, original) = record_with_rules(Mode::Master, rules.clone(), 9, 2000);
+        let mut file = Vec::new();
+        replay.write(&mut file).unwrap();
+        let read = Replay::read(&file[..]).unwrap();
+        assert_eq!(read.rules, rules);
+        let played = read.play();
+        assert_eq!(played.board().board(), original.board().board());
+        assert_eq!(played.held(), original.held());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let file = format!("{} {}\nmode normal\n", MAGIC, REPLAY_VERSION + 1);
+        assert!(Replay::read(file.as_bytes()).is_err());
+    }
+}
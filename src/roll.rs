@@ -0,0 +1,243 @@
+// The credit roll: once the difficulty curve is done, the stack is cleared
+// away and the player has to survive a while longer at full speed while the
+// staff roll plays.  The harder variants hide the stack as it is built, and
+// lines cleared during the roll count towards the grade.
+
+use crate::game::FRAMES_PER_SECOND;
+
+pub const ROLL_FRAMES: usize = 55 * FRAMES_PER_SECOND as usize;
+// The pause between clearing away the stack and the first piece of the roll.
+pub const ROLL_DELAY_FRAMES: usize = 3 * FRAMES_PER_SECOND as usize;
+pub const FADE_FRAMES: usize = 5 * FRAMES_PER_SECOND as usize;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CreditRoll {
+    Off,       // the game ends as soon as the curve is done
+    Normal,    // the stack stays visible
+    Fading,    // locked blocks disappear after FADE_FRAMES
+    Invisible, // locked blocks disappear at once
+}
+
+impl CreditRoll {
+    pub const ALL: [CreditRoll; 4] = [
+        CreditRoll::Off,
+        CreditRoll::Normal,
+        CreditRoll::Fading,
+        CreditRoll::Invisible,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CreditRoll::Off => "off",
+            CreditRoll::Normal => "normal",
+            CreditRoll::Fading => "fading",
+            CreditRoll::Invisible => "invisible",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<CreditRoll> {
+        CreditRoll::ALL.iter().cloned().find(|r| r.name() == name)
+    }
+    // How long blocks locked during the roll stay visible.
+    pub fn vanish(self) -> Option<usize> {
+        match self {
+            CreditRoll::Off | CreditRoll::Normal => None,
+            CreditRoll::Fading => Some(FADE_FRAMES),
+            CreditRoll::Invisible => Some(0),
+        }
+    }
+    // Grade bonuses, in hundredths of a grade, for clearing 1 to 4 lines
+    // during the roll.
+    pub fn line_bonus(self, lines: usize) -> usize {
+        const FADING: [usize; 4] = [4, 8, 12, 26];
+        const INVISIBLE: [usize; 4] = [10, 20, 30, 100];
+        match (self, lines.min(4)) {
+            (_, 0) => 0,
+            (CreditRoll::Fading, n) => FADING[n - 1],
+            (CreditRoll::Invisible, n) => INVISIBLE[n - 1],
+            _ => 0,
+        }
+    }
+    // Likewise, for surviving to the end.
+    pub fn clear_bonus(self) -> usize {
+        match self {
+            CreditRoll::Fading => 50,
+            CreditRoll::Invisible => 160,
+            _ => 0,
+        }
+    }
+}
+
+// A game's way through the credit roll: the pause before it, the roll
+// itself, and the grade bonus earned along the way.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Roll {
+    credit_roll: CreditRoll,
+    delay: usize,          // frames into the pause
+    frames: Option<usize>, // frames into the roll, once it has begun
+    bonus: usize,          // in hundredths of a grade
+}
+
+impl Roll {
+    pub fn new(credit_roll: CreditRoll) -> Self {
+        Roll {
+            credit_roll,
+            delay: 0,
+            frames: None,
+            bonus: 0,
+        }
+    }
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+    pub fn rolling(&self) -> bool {
+        self.frames.is_some()
+    }
+    pub fn frames_left(&self) -> Option<usize> {
+        self.frames.map(|frames| ROLL_FRAMES - frames)
+    }
+    // How long blocks locked during the roll stay visible.
+    pub fn vanish(&self) -> Option<usize> {
+        self.credit_roll.vanish()
+    }
+    // Call once a frame during the pause.  Returns true once the roll has
+    // begun.
+    pub fn wait(&mut self) -> bool {
+        if self.frames.is_none() {
+            if self.delay < ROLL_DELAY_FRAMES {
+                self.delay += 1;
+                return false;
+            }
+            self.frames = Some(0);
+        }
+        true
+    }
+    // Call once a frame during the roll.  Returns true once it is over.
+    pub fn tick(&mut self) -> bool {
+        match self.frames {
+            Some(frames) if frames >= ROLL_FRAMES => true,
+            Some(frames) => {
+                self.frames = Some(frames + 1);
+                false
+            }
+            None => false,
+        }
+    }
+    // Call every time a piece locks during the roll.
+    pub fn clear_lines(&mut self, lines: usize) {
+        self.bonus += self.credit_roll.line_bonus(lines);
+    }
+    // The grade bonus earned in the roll, in hundredths of a grade.
+    pub fn bonus(&self, survived: bool) -> usize {
+        if survived {
+            self.bonus + self.credit_roll.clear_bonus()
+        } else {
+            self.bonus
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::board::BoardState;
+    use crate::piece::{Orientation, Tetrhombino, TetrhombinoState, START_POSITION};
+    use crate::rotation::RotationSystem;
+
+    #[test]
+    fn pauses_then_rolls_then_ends() {
+        let mut roll = Roll::new(CreditRoll::Normal);
+        for n in 1..=ROLL_DELAY_FRAMES {
+            assert!(!roll.wait());
+            assert_eq!(roll.delay(), n);
+            assert_eq!(roll.frames_left(), None);
+        }
+        assert!(roll.wait());
+        assert!(roll.rolling());
+        assert_eq!(roll.frames_left(), Some(ROLL_FRAMES));
+        for _ in 0..ROLL_FRAMES {
+            assert!(!roll.tick());
+        }
+        assert_eq!(roll.frames_left(), Some(0));
+        assert!(roll.tick());
+    }
+
+    #[test]
+    fn does_not_count_down_during_the_pause() {
+        let mut roll = Roll::new(CreditRoll::Normal);
+        assert!(!roll.tick());
+        assert_eq!(roll.frames_left(), None);
+        assert_eq!(roll.delay(), 0);
+    }
+
+    // Locks an O at the bottom of a board set up for `credit_roll`.
+    fn lock_piece(credit_roll: CreditRoll) -> BoardState {
+        let mut board = BoardState::new(Arc::new(RotationSystem::classic()));
+        board.set_vanish(Roll::new(credit_roll).vanish());
+        board.spawn(TetrhombinoState {
+            tetrhombino: Tetrhombino::O,
+            orientation: Orientation::Start,
+            position: START_POSITION,
+        });
+        while board.fall() {}
+        board.lock();
+        board
+    }
+
+    fn visibility(board: &BoardState) -> Vec<Option<usize>> {
+        let mut visibility = Vec::new();
+        for (column, visible) in board.board().iter().zip(board.visibility().iter()) {
+            for (block, visible) in column.iter().zip(visible.iter()) {
+                if block.is_some() {
+                    visibility.push(*visible);
+                }
+            }
+        }
+        visibility
+    }
+
+    #[test]
+    fn normal_blocks_stay_visible() {
+        let mut board = lock_piece(CreditRoll::Normal);
+        board.tick();
+        assert_eq!(visibility(&board), vec![None; 4]);
+    }
+
+    #[test]
+    fn fading_blocks_disappear_after_a_while() {
+        let mut board = lock_piece(CreditRoll::Fading);
+        assert_eq!(visibility(&board), vec![Some(FADE_FRAMES); 4]);
+        for _ in 0..FADE_FRAMES - 1 {
+            board.tick();
+        }
+        assert_eq!(visibility(&board), vec![Some(1); 4]);
+        board.tick();
+        assert_eq!(visibility(&board), vec![Some(0); 4]);
+    }
+
+    #[test]
+    fn invisible_blocks_disappear_at_once() {
+        let board = lock_piece(CreditRoll::Invisible);
+        assert_eq!(visibility(&board), vec![Some(0); 4]);
+    }
+
+    #[test]
+    fn surviving_earns_the_clear_bonus() {
+        let mut roll = Roll::new(CreditRoll::Fading);
+        roll.clear_lines(0);
+        roll.clear_lines(1);
+        roll.clear_lines(4);
+        assert_eq!(roll.bonus(false), 4 + 26);
+        assert_eq!(roll.bonus(true), 4 + 26 + 50);
+
+        let mut roll = Roll::new(CreditRoll::Invisible);
+        roll.clear_lines(4);
+        assert_eq!(roll.bonus(false), 100);
+        assert_eq!(roll.bonus(true), 100 + 160);
+
+        let mut roll = Roll::new(CreditRoll::Normal);
+        roll.clear_lines(4);
+        assert_eq!(roll.bonus(true), 0);
+    }
+}
@@ -0,0 +1,396 @@
+// A rotation system: the shape of every piece in every orientation, and the
+// rules for kicking a piece that does not fit after rotating.  These are read
+// from data files; the built-in ones are in the `rotation/` directory.
+//
+// The format is line-oriented text, with `#` starting a comment:
+//
+//   rotation-system 1
+//   name classic
+//   floor-kick-limit 0
+//   shape PIECE ORIENTATIONS X,Y X,Y X,Y X,Y
+//   kick PIECES FROM TO CONDITION [wall X,Y...] [floor X,Y...]
+//
+// PIECES, ORIENTATIONS, FROM and TO are comma-separated lists, or `*` for all
+// of them.  X,Y offsets are relative to the piece's position, and at most
+// MAX_OFFSET either way.  Every piece needs a shape for every orientation.
+// Kick rules are tried in order, and the first one matching the piece and the
+// rotation is used; if none match, the piece does not kick.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::piece::{Orientation, Position, Tetrhombino, TetrhombinoState};
+
+pub const ROTATION_SYSTEM_VERSION: u32 = 1;
+const MAGIC: &str = "rotation-system";
+pub const MAX_OFFSET: i8 = 4;
+
+// When wall kicks are allowed, checked relative to the rotated piece.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum KickCondition {
+    Always,
+    Never,
+    CenterClear,        // the piece's own column, one cell either side, is empty
+    Occupied(Position), // this cell is filled
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct KickRule {
+    pub pieces: [bool; 7], // indexed by Tetrhombino::index
+    pub from: [bool; 4],   // indexed by Orientation::index
+    pub to: [bool; 4],
+    pub condition: KickCondition,
+    pub wall: Vec<Position>,
+    pub floor: Vec<Position>,
+}
+
+impl KickRule {
+    fn matches(&self, piece: Tetrhombino, from: Orientation, to: Orientation) -> bool {
+        self.pieces[piece.index()] && self.from[from.index()] && self.to[to.index()]
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RotationSystem {
+    name: String,
+    shapes: [[[Position; 4]; 4]; 7], // by Tetrhombino::index, Orientation::index
+    kicks: Vec<KickRule>,
+    floor_kick_limit: usize, // per piece
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_position(word: &str) -> Option<Position> {
+    let mut parts = word.splitn(2, ',');
+    let x: i8 = parts.next()?.parse().ok()?;
+    let y: i8 = parts.next()?.parse().ok()?;
+    let range = -MAX_OFFSET..=MAX_OFFSET;
+    if !range.contains(&x) || !range.contains(&y) {
+        return None;
+    }
+    Some((x, y))
+}
+
+fn position_name(position: Position) -> String {
+    format!("{},{}", position.0, position.1)
+}
+
+// The inverse of `parse_pieces` and `parse_orientations`.
+fn list_name(included: &[bool], names: &[&str]) -> String {
+    if included.iter().all(|&i| i) {
+        return "*".to_string();
+    }
+    let names: Vec<&str> = names
+        .iter()
+        .zip(included.iter())
+        .filter(|&(_, &i)| i)
+        .map(|(&name, _)| name)
+        .collect();
+    names.join(",")
+}
+
+fn parse_orientations(word: &str) -> Option<[bool; 4]> {
+    let mut result = [word == "*"; 4];
+    if word != "*" {
+        for name in word.split(',') {
+            result[Orientation::from_name(name)?.index()] = true;
+        }
+    }
+    Some(result)
+}
+
+fn parse_pieces(word: &str) -> Option<[bool; 7]> {
+    let mut result = [word == "*"; 7];
+    if word != "*" {
+        for name in word.split(',') {
+            result[Tetrhombino::from_name(name)?.index()] = true;
+        }
+    }
+    Some(result)
+}
+
+fn condition_name(condition: KickCondition) -> String {
+    match condition {
+        KickCondition::Always => "always".to_string(),
+        KickCondition::Never => "never".to_string(),
+        KickCondition::CenterClear => "center-clear".to_string(),
+        KickCondition::Occupied(position) => format!("occupied:{}", position_name(position)),
+    }
+}
+
+fn parse_condition(word: &str) -> Option<KickCondition> {
+    match word {
+        "always" => Some(KickCondition::Always),
+        "never" => Some(KickCondition::Never),
+        "center-clear" => Some(KickCondition::CenterClear),
+        _ => parse_position(word.strip_prefix("occupied:")?).map(KickCondition::Occupied),
+    }
+}
+
+fn parse_kick(words: &[&str]) -> Option<KickRule> {
+    let mut rule = KickRule {
+        pieces: parse_pieces(words.first()?)?,
+        from: parse_orientations(words.get(1)?)?,
+        to: parse_orientations(words.get(2)?)?,
+        condition: parse_condition(words.get(3)?)?,
+        wall: Vec::new(),
+        floor: Vec::new(),
+    };
+    let mut list = None;
+    for word in words[4..].iter() {
+        match *word {
+            "wall" => list = Some(&mut rule.wall),
+            "floor" => list = Some(&mut rule.floor),
+            _ => list.as_mut()?.push(parse_position(word)?),
+        }
+    }
+    Some(rule)
+}
+
+impl RotationSystem {
+    pub fn classic() -> Self {
+        Self::parse_builtin(include_str!("../rotation/classic.txt"))
+    }
+    pub fn ti() -> Self {
+        Self::parse_builtin(include_str!("../rotation/ti.txt"))
+    }
+    pub fn srs() -> Self {
+        Self::parse_builtin(include_str!("../rotation/srs.txt"))
+    }
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Self::classic()),
+            "ti" => Some(Self::ti()),
+            "srs" => Some(Self::srs()),
+            _ => None,
+        }
+    }
+    fn parse_builtin(text: &str) -> Self {
+        Self::read(text.as_bytes()).expect("built-in rotation system is invalid")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(input: R) -> io::Result<Self> {
+        let mut name = None;
+        let mut floor_kick_limit = 0;
+        let mut shapes = [[[(0, 0); 4]; 4]; 7];
+        let mut have_shape = [[false; 4]; 7];
+        let mut kicks = Vec::new();
+        let mut seen_header = false;
+        for line in input.lines() {
+            let line = line?;
+            let content = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = content.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let bad_line = || invalid(format!("bad rotation system line: {:?}", line));
+            if !seen_header {
+                match words.as_slice() {
+                    [MAGIC, version] if version.parse() == Ok(ROTATION_SYSTEM_VERSION) => {}
+                    _ => return Err(invalid(format!("not a rotation system: {:?}", line))),
+                }
+                seen_header = true;
+                continue;
+            }
+            match words.as_slice() {
+                ["name", n] => name = Some(n.to_string()),
+                ["floor-kick-limit", n] => floor_kick_limit = n.parse().map_err(|_| bad_line())?,
+                ["shape", piece, orientations, cells @ ..] if cells.len() == 4 => {
+                    let piece = Tetrhombino::from_name(piece).ok_or_else(bad_line)?;
+                    let orientations = parse_orientations(orientations).ok_or_else(bad_line)?;
+                    let mut shape = [(0, 0); 4];
+                    for (cell, word) in shape.iter_mut().zip(cells.iter()) {
+                        *cell = parse_position(word).ok_or_else(bad_line)?;
+                    }
+                    for orientation in Orientation::ALL.iter() {
+                        if orientations[orientation.index()] {
+                            shapes[piece.index()][orientation.index()] = shape;
+                            have_shape[piece.index()][orientation.index()] = true;
+                        }
+                    }
+                }
+                ["kick", rest @ ..] => kicks.push(parse_kick(rest).ok_or_else(bad_line)?),
+                _ => return Err(bad_line()),
+            }
+        }
+
+        for piece in Tetrhombino::ALL.iter() {
+            for orientation in Orientation::ALL.iter() {
+                if !have_shape[piece.index()][orientation.index()] {
+                    return Err(invalid(format!(
+                        "rotation system has no shape for {} {}",
+                        piece.name(),
+                        orientation.name()
+                    )));
+                }
+            }
+        }
+        Ok(RotationSystem {
+            name: name.ok_or_else(|| invalid("rotation system has no name".to_string()))?,
+            shapes,
+            kicks,
+            floor_kick_limit,
+        })
+    }
+
+    // Writes the system back out in the format `read` understands, one shape
+    // per piece and orientation.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let pieces: Vec<&str> = Tetrhombino::ALL.iter().map(|t| t.name()).collect();
+        let orientations: Vec<&str> = Orientation::ALL.iter().map(|o| o.name()).collect();
+        writeln!(out, "{} {}", MAGIC, ROTATION_SYSTEM_VERSION)?;
+        writeln!(out, "name {}", self.name)?;
+        writeln!(out, "floor-kick-limit {}", self.floor_kick_limit)?;
+        for piece in Tetrhombino::ALL.iter() {
+            for orientation in Orientation::ALL.iter() {
+                let cells: Vec<String> = self.shapes[piece.index()][orientation.index()]
+                    .iter()
+                    .map(|&cell| position_name(cell))
+                    .collect();
+                writeln!(
+                    out,
+                    "shape {} {} {}",
+                    piece.name(),
+                    orientation.name(),
+                    cells.join(" ")
+                )?;
+            }
+        }
+        for rule in self.kicks.iter() {
+            write!(
+                out,
+                "kick {} {} {} {}",
+                list_name(&rule.pieces, &pieces),
+                list_name(&rule.from, &orientations),
+                list_name(&rule.to, &orientations),
+                condition_name(rule.condition)
+            )?;
+            for (label, offsets) in [("wall", &rule.wall), ("floor", &rule.floor)].iter() {
+                if !offsets.is_empty() {
+                    write!(out, " {}", label)?;
+                    for &offset in offsets.iter() {
+                        write!(out, " {}", position_name(offset))?;
+                    }
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn floor_kick_limit(&self) -> usize {
+        self.floor_kick_limit
+    }
+    pub fn occupied_places(&self, state: TetrhombinoState) -> [Position; 4] {
+        let mut result = self.shapes[state.tetrhombino.index()][state.orientation.index()];
+        for elt in result.iter_mut() {
+            elt.0 += state.position.0;
+            elt.1 += state.position.1;
+        }
+        result
+    }
+    pub fn kick_rule(
+        &self,
+        piece: Tetrhombino,
+        from: Orientation,
+        to: Orientation,
+    ) -> Option<&KickRule> {
+        self.kicks.iter().find(|rule| rule.matches(piece, from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn srs_with(line: &str) -> io::Result<RotationSystem> {
+        let text = format!("{}\n{}\n", include_str!("../rotation/srs.txt"), line);
+        RotationSystem::read(text.as_bytes())
+    }
+
+    #[test]
+    fn builtins_parse() {
+        for name in ["classic", "ti", "srs"].iter() {
+            assert_eq!(RotationSystem::builtin(name).unwrap().name(), *name);
+        }
+    }
+
+    #[test]
+    fn write_then_read_round_trips() {
+        for name in ["classic", "ti", "srs"].iter() {
+            let system = RotationSystem::builtin(name).unwrap();
+            let mut file = Vec::new();
+            system.write(&mut file).unwrap();
+            assert_eq!(RotationSystem::read(&file[..]).unwrap(), system);
+        }
+    }
+
+    #[test]
+    fn offsets_up_to_the_limit_are_accepted() {
+        assert!(srs_with("kick * * * occupied:4,-4 wall 4,0 -4,0 floor 0,4").is_ok());
+    }
+
+    #[test]
+    fn offsets_past_the_limit_are_rejected() {
+        for line in [
+            "shape O * 127,0 0,0 0,0 0,0",
+            "shape O * 0,-128 0,0 0,0 0,0",
+            "kick * * * always wall 5,0",
+            "kick * * * always floor 0,-5",
+            "kick * * * occupied:0,5",
+        ]
+        .iter()
+        {
+            let err = srs_with(line).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", line);
+        }
+    }
+
+    const HALF_TURNS: [(Orientation, Orientation); 4] = [
+        (Orientation::Start, Orientation::Both),
+        (Orientation::Both, Orientation::Start),
+        (Orientation::Right, Orientation::Left),
+        (Orientation::Left, Orientation::Right),
+    ];
+
+    #[test]
+    fn classic_and_ti_half_turns_never_kick() {
+        for system in [RotationSystem::classic(), RotationSystem::ti()].iter() {
+            for &piece in Tetrhombino::ALL.iter() {
+                for &(from, to) in HALF_TURNS.iter() {
+                    let rule = system.kick_rule(piece, from, to).unwrap();
+                    assert_eq!(rule.condition, KickCondition::Never);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn srs_half_turns_have_their_own_table() {
+        let srs = RotationSystem::srs();
+        for &piece in Tetrhombino::ALL.iter() {
+            if piece == Tetrhombino::O {
+                continue;
+            }
+            let half = srs.kick_rule(piece, Orientation::Start, Orientation::Both);
+            assert_eq!(
+                half.unwrap().wall,
+                [(0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]
+            );
+            let half = srs.kick_rule(piece, Orientation::Right, Orientation::Left);
+            assert_eq!(half.unwrap().wall, [(1, 0), (1, 2), (1, 1), (0, 2), (0, 1)]);
+            let quarter = srs.kick_rule(piece, Orientation::Start, Orientation::Right);
+            assert_eq!(quarter.unwrap().wall.len(), 4);
+        }
+    }
+}
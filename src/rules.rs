@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::grade::Grading;
+use crate::lock::LockReset;
+use crate::roll::CreditRoll;
+use crate::rotation::RotationSystem;
+
+pub const MAX_PREVIEW: usize = 6;
+
+// Ruleset switches that differ between modes but have nothing to do with
+// how fast the game goes.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Rules {
+    pub hold: bool,
+    pub initial_hold: bool, // holding the hold button through ARE holds on spawn
+    pub preview: usize,     // length of the next queue; see `preview()`
+    pub rotation: Arc<RotationSystem>,
+    pub lock_reset: LockReset,
+    pub grading: Grading,
+    pub credit_roll: CreditRoll,
+}
+
+impl Rules {
+    // TGM1 and TGM2: no hold, and grades go by score.
+    pub fn classic() -> Self {
+        Rules {
+            hold: false,
+            initial_hold: false,
+            preview: 1,
+            rotation: Arc::new(RotationSystem::classic()),
+            lock_reset: LockReset::Step,
+            grading: Grading::Score,
+            credit_roll: CreditRoll::Normal,
+        }
+    }
+    // TGM3, though with TGM2's grade points.
+    pub fn modern() -> Self {
+        Rules {
+            hold: true,
+            initial_hold: true,
+            preview: 3,
+            rotation: Arc::new(RotationSystem::ti()),
+            lock_reset: LockReset::Step,
+            grading: Grading::Points,
+            credit_roll: CreditRoll::Fading,
+        }
+    }
+    // Modern, but with nothing to play for except the clock.
+    pub fn sprint() -> Self {
+        Rules {
+            grading: Grading::Off,
+            credit_roll: CreditRoll::Off,
+            ..Self::modern()
+        }
+    }
+    // The length of the next queue, clamped to 1..=MAX_PREVIEW.
+    pub fn preview(&self) -> usize {
+        self.preview.clamp(1, MAX_PREVIEW)
+    }
+    // Clamped to 1..=MAX_PREVIEW.
+    pub fn with_preview(mut self, preview: usize) -> Self {
+        self.preview = preview.clamp(1, MAX_PREVIEW);
+        self
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
@@ -0,0 +1,116 @@
+// TGM-style scoring.  Each piece that clears lines is worth
+//
+//   (ceil((level + lines) / 4) + soft + 2 * sonic) * lines * combo * bravo
+//
+// where `level` is the level before the clear, `soft` is the number of frames
+// soft drop was held for the piece, `sonic` is the furthest it fell in one
+// sonic drop, `bravo` is 4 if the clear emptied the board and 1 otherwise,
+// and `combo` starts at 1 and goes up by 2 * lines - 2 with every clear in a
+// row, going back to 1 whenever a piece locks without clearing anything.
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Score {
+    score: usize,
+    combo: usize,
+    soft_frames: usize, // for the current piece
+    sonic_rows: usize,  // likewise
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Score {
+            score: 0,
+            combo: 1,
+            soft_frames: 0,
+            sonic_rows: 0,
+        }
+    }
+    pub fn score(&self) -> usize {
+        self.score
+    }
+    pub fn spawn(&mut self) {
+        self.soft_frames = 0;
+        self.sonic_rows = 0;
+    }
+    pub fn soft_drop(&mut self) {
+        self.soft_frames += 1;
+    }
+    pub fn sonic_drop(&mut self, rows: usize) {
+        self.sonic_rows = self.sonic_rows.max(rows);
+    }
+    // `level` is the level before the lines were cleared.
+    pub fn lock(&mut self, level: usize, lines: usize, bravo: bool) {
+        if lines == 0 {
+            self.combo = 1;
+            return;
+        }
+        self.combo += 2 * lines - 2;
+        let bravo = if bravo { 4 } else { 1 };
+        let base = (level + lines).div_ceil(4) + self.soft_frames + 2 * self.sonic_rows;
+        self.score += base * lines * self.combo * bravo;
+    }
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single() {
+        let mut score = Score::new();
+        score.lock(0, 1, false);
+        assert_eq!(score.score(), 1);
+        score.spawn();
+        for _ in 0..5 {
+            score.soft_drop();
+        }
+        score.lock(10, 1, false);
+        // ceil(11 / 4) + 5 soft drop frames
+        assert_eq!(score.score(), 1 + 8);
+    }
+
+    #[test]
+    fn tetris_combo() {
+        let mut score = Score::new();
+        score.lock(100, 4, false);
+        // ceil(104 / 4) * 4 lines * combo 7
+        assert_eq!(score.score(), 728);
+        score.spawn();
+        score.lock(104, 4, false);
+        // ceil(108 / 4) * 4 lines * combo 13
+        assert_eq!(score.score(), 728 + 1404);
+        score.spawn();
+        score.lock(108, 0, false);
+        score.spawn();
+        score.lock(108, 1, false);
+        assert_eq!(score.score(), 728 + 1404 + 28);
+    }
+
+    #[test]
+    fn bravo() {
+        let mut score = Score::new();
+        score.sonic_drop(4);
+        score.sonic_drop(10);
+        score.sonic_drop(6);
+        score.lock(0, 2, true);
+        // (ceil(2 / 4) + 2 * 10 sonic rows) * 2 lines * combo 3 * bravo 4
+        assert_eq!(score.score(), 504);
+    }
+
+    #[test]
+    fn drops_only_count_for_the_current_piece() {
+        let mut score = Score::new();
+        score.soft_drop();
+        score.sonic_drop(10);
+        score.lock(0, 0, false);
+        score.spawn();
+        score.lock(0, 1, false);
+        assert_eq!(score.score(), 1);
+    }
+}
@@ -0,0 +1,179 @@
+// The game clock.  It starts when the first piece spawns, counts every frame
+// after that until the game ends or the credit roll begins, and takes a split
+// at the end of each section, so runs can be compared section by section.
+
+use crate::game::FRAMES_PER_SECOND;
+use crate::level::SECTION_LENGTH;
+
+pub const LINES_PER_SECTION: usize = 10;
+
+// What a mode's sections are measured in.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Sections {
+    Levels, // SECTION_LENGTH levels each
+    Lines,  // LINES_PER_SECTION lines each
+}
+
+impl Sections {
+    pub fn section(self, level: usize, lines: usize) -> usize {
+        match self {
+            Sections::Levels => level / SECTION_LENGTH,
+            Sections::Lines => lines / LINES_PER_SECTION,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Timer {
+    started: bool,
+    finished: bool,
+    frames: u64,
+    section_start: u64,
+    splits: Vec<u64>, // how long each finished section took
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            started: false,
+            finished: false,
+            frames: 0,
+            section_start: 0,
+            splits: Vec::new(),
+        }
+    }
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+    // Safe to call on every spawn.
+    pub fn start(&mut self) {
+        self.started = true;
+    }
+    pub fn tick(&mut self) {
+        if self.started && !self.finished {
+            self.frames += 1;
+        }
+    }
+    // Call with the index of the section the player is now in.
+    pub fn reach_section(&mut self, section: usize) {
+        while self.splits.len() < section {
+            self.splits.push(self.frames - self.section_start);
+            self.section_start = self.frames;
+        }
+    }
+    // Stops the clock for good, counting the last section even if it wasn't
+    // a full one.
+    pub fn finish(&mut self) {
+        if self.frames > self.section_start {
+            self.splits.push(self.frames - self.section_start);
+            self.section_start = self.frames;
+        }
+        self.finished = true;
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// As mm:ss:cc.
+pub fn format_time(frames: u64) -> String {
+    let centiseconds = frames * 100 / FRAMES_PER_SECOND;
+    format!(
+        "{:02}:{:02}:{:02}",
+        centiseconds / 6000,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(timer: &mut Timer, frames: u64) {
+        for _ in 0..frames {
+            timer.tick();
+        }
+    }
+
+    #[test]
+    fn waits_for_the_first_piece() {
+        let mut timer = Timer::new();
+        run(&mut timer, 30);
+        assert_eq!(timer.frames(), 0);
+        timer.start();
+        run(&mut timer, 30);
+        assert_eq!(timer.frames(), 30);
+    }
+
+    #[test]
+    fn splits_at_level_boundaries() {
+        let sections = Sections::Levels;
+        let mut timer = Timer::new();
+        timer.start();
+        run(&mut timer, 100);
+        timer.reach_section(sections.section(99, 0));
+        assert!(timer.splits().is_empty());
+        timer.reach_section(sections.section(100, 0));
+        assert_eq!(timer.splits(), &[100]);
+        run(&mut timer, 50);
+        // A big enough clear can skip a whole section.
+        timer.reach_section(sections.section(301, 0));
+        assert_eq!(timer.splits(), &[100, 50, 0]);
+    }
+
+    #[test]
+    fn splits_at_line_boundaries() {
+        let sections = Sections::Lines;
+        let mut timer = Timer::new();
+        timer.start();
+        run(&mut timer, 40);
+        timer.reach_section(sections.section(500, 9));
+        assert!(timer.splits().is_empty());
+        timer.reach_section(sections.section(500, 10));
+        assert_eq!(timer.splits(), &[40]);
+        run(&mut timer, 25);
+        timer.reach_section(sections.section(0, 22));
+        assert_eq!(timer.splits(), &[40, 25]);
+    }
+
+    #[test]
+    fn finishing_early_keeps_the_partial_section() {
+        let mut timer = Timer::new();
+        timer.start();
+        run(&mut timer, 100);
+        timer.reach_section(1);
+        run(&mut timer, 30);
+        timer.finish();
+        assert_eq!(timer.splits(), &[100, 30]);
+        run(&mut timer, 30);
+        assert_eq!(timer.frames(), 130);
+    }
+
+    #[test]
+    fn finishing_on_a_boundary_adds_no_empty_split() {
+        let sections = Sections::Lines;
+        let mut timer = Timer::new();
+        timer.start();
+        for _ in 0..4 {
+            run(&mut timer, 60);
+            let section = timer.splits().len() + 1;
+            timer.reach_section(sections.section(0, section * LINES_PER_SECTION));
+        }
+        timer.finish();
+        assert_eq!(timer.splits(), &[60, 60, 60, 60]);
+        assert_eq!(timer.frames(), 240);
+    }
+
+    #[test]
+    fn formats_minutes_seconds_and_centiseconds() {
+        assert_eq!(format_time(0), "00:00:00");
+        assert_eq!(format_time(FRAMES_PER_SECOND * 83 + 30), "01:23:50");
+    }
+}